@@ -0,0 +1,40 @@
+// an example material, a tinted reflection that brightens towards grazing angles.
+// material.params.rgb is the tint and material.params.w the fresnel power.
+#version 450
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec3 v_position;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view;
+    mat4 proj;
+    mat4 model;
+    vec4 sh[9];
+    mat4 env_rotation;
+    vec3 camera_position;
+    float time;
+    float env_intensity;
+    uint encode_srgb;
+} frame;
+
+layout(set = 0, binding = 1) uniform Material {
+    vec4 params;
+} material;
+
+layout(set = 1, binding = 0) uniform samplerCube environment;
+
+void main() {
+    vec3 n = normalize(v_normal);
+    vec3 to_eye = normalize(frame.camera_position - v_position);
+    vec3 reflected = texture(environment, mat3(frame.env_rotation) * reflect(-to_eye, n)).rgb;
+
+    float power = material.params.w > 0.0 ? material.params.w : 5.0;
+    float fresnel = pow(1.0 - max(dot(n, to_eye), 0.0), power);
+    vec3 tint = material.params.rgb;
+
+    vec3 color = mix(reflected * tint, reflected, fresnel) * frame.env_intensity;
+    // a close enough gamma 2.2 for when the swapchain isn't srgb.
+    f_color = vec4(frame.encode_srgb == 1 ? pow(color, vec3(1.0 / 2.2)) : color, 1.0);
+}
@@ -0,0 +1,63 @@
+// from the teapot example
+
+#version 450
+
+layout(location = 0) in vec3 v_normal; // world space
+layout(location = 1) in vec3 v_position; // world space
+
+layout(location = 0) out vec4 f_color;
+
+// the material contract, custom shaders passed with --shader may leave out bindings and
+// the tail of a block, but what they do declare has to match exactly. see the readme.
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view;
+    mat4 proj;
+    mat4 model; // object to world
+    vec4 sh[9]; // skybox irradiance, already convolved with the cosine lobe
+    mat4 env_rotation; // spins the environment around the model
+    vec3 camera_position; // world space
+    float time; // seconds since startup
+    float env_intensity; // brightness multiplier for everything sampled from the environment
+    uint encode_srgb; // 1 when the swapchain isn't srgb and the output has to be encoded here
+} frame;
+
+layout(set = 0, binding = 1) uniform Material {
+    vec4 params; // free for custom shaders, set with --material-params
+    float diffuse; // how much diffuse irradiance to mix in to the reflection
+} material;
+
+layout(set = 1, binding = 0) uniform samplerCube environment;
+
+// evaluate the irradiance sh at normal n
+vec3 irradiance(vec3 n) {
+    return frame.sh[0].rgb * 0.282095
+        + frame.sh[1].rgb * 0.488603 * n.y
+        + frame.sh[2].rgb * 0.488603 * n.z
+        + frame.sh[3].rgb * 0.488603 * n.x
+        + frame.sh[4].rgb * 1.092548 * n.x * n.y
+        + frame.sh[5].rgb * 1.092548 * n.y * n.z
+        + frame.sh[6].rgb * 0.315392 * (3.0 * n.z * n.z - 1.0)
+        + frame.sh[7].rgb * 1.092548 * n.x * n.z
+        + frame.sh[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+}
+
+// linear light to the srgb transfer curve, for swapchains that don't do it themselves
+vec3 encode_srgb(vec3 c) {
+    c = max(c, vec3(0.0));
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+// rotate a lookup direction in to the environment's frame
+vec3 env_dir(vec3 dir) {
+    return mat3(frame.env_rotation) * dir;
+}
+
+void main() {
+    vec3 n = normalize(v_normal);
+    vec3 view_dir = normalize(v_position - frame.camera_position);
+    vec4 specular = texture(environment, env_dir(reflect(view_dir, n)));
+    // lambertian radiance is irradiance over pi, the cubemap is sampled as srgb so this is linear too.
+    vec3 diffuse = max(irradiance(env_dir(n)), vec3(0.0)) / 3.14159265;
+    vec3 color = mix(specular.rgb, diffuse, material.diffuse) * frame.env_intensity;
+    f_color = vec4(frame.encode_srgb == 1 ? encode_srgb(color) : color, 1.0);
+}
@@ -0,0 +1,29 @@
+// from the teapot example
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+// both in world space, the same space as the camera position and the environment.
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec3 v_position;
+
+// see the readme for what every member means, material shaders rely on this layout.
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view;
+    mat4 proj;
+    mat4 model;
+    vec4 sh[9];
+    mat4 env_rotation;
+    vec3 camera_position;
+    float time;
+    float env_intensity;
+} frame;
+
+void main() {
+    vec4 world = frame.model * vec4(position, 1.0);
+    // the inverse transpose keeps normals perpendicular under non uniform scales.
+    v_normal = transpose(inverse(mat3(frame.model))) * normal;
+    v_position = world.xyz;
+    gl_Position = frame.proj * frame.view * world;
+}
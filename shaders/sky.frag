@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec3 v_direction;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 inv_view_proj;
+    mat4 env_rotation;
+    float env_intensity;
+    uint encode_srgb;
+} uniforms;
+
+layout(set = 0, binding = 1) uniform samplerCube cubetex;
+
+// linear light to the srgb transfer curve, for swapchains that don't do it themselves
+vec3 encode_srgb(vec3 c) {
+    c = max(c, vec3(0.0));
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+void main() {
+    vec3 dir = mat3(uniforms.env_rotation) * normalize(v_direction);
+    vec3 color = texture(cubetex, dir).rgb * uniforms.env_intensity;
+    f_color = vec4(uniforms.encode_srgb == 1 ? encode_srgb(color) : color, 1.0);
+}
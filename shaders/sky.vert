@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+
+layout(location = 0) out vec3 v_direction;
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 inv_view_proj; // inverse of the translation free view projection
+    mat4 env_rotation;
+    float env_intensity;
+    uint encode_srgb;
+} uniforms;
+
+void main() {
+    // fullscreen triangle pinned to the far plane, so it only fills
+    // pixels the model left at the cleared depth of 1.0.
+    gl_Position = vec4(position.xy, 1.0, 1.0);
+    // the homogeneous un-projection interpolates linearly
+    // and keeps the view direction after normalizing.
+    v_direction = (uniforms.inv_view_proj * gl_Position).xyz;
+}
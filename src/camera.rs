@@ -0,0 +1,441 @@
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, Rotation, Rotation3,
+    Vector3, Zero,
+};
+use std::collections::HashSet;
+use winit::{ElementState, VirtualKeyCode};
+
+/// factor the orbit distance changes by per notch of the mouse wheel.
+static DOLLY_STEP: f32 = 1.1;
+
+/// pixels of touchpad scrolling that count as one wheel notch.
+static PIXELS_PER_NOTCH: f32 = 20.0;
+
+/// the camera never gets closer to the target than this.
+static MIN_DISTANCE: f32 = 0.01;
+
+/// factor the field of view changes by per notch of the mouse wheel in fly mode.
+static ZOOM_STEP: f32 = 1.05;
+
+/// the field of view is kept between these, in degrees.
+static MIN_FOV: f32 = 5.0;
+static MAX_FOV: f32 = 120.0;
+
+/// the furthest the far plane may be from the near plane, as a ratio. perspective depth
+/// crowds in to the far end and a 16 bit depth buffer has little to spare, past this
+/// the back of the model starts z fighting.
+static MAX_DEPTH_RATIO: f32 = 1000.0;
+
+/// fly speed in world units per second, the model is scaled to fit in 0.3.
+static FLY_SPEED: f32 = 0.5;
+
+/// speed multipliers while shift or control are held.
+static FAST: f32 = 4.0;
+static SLOW: f32 = 0.25;
+
+/// how quickly the fly velocity catches up with the keys, per second.
+/// speeding up is snappier than coasting to a stop.
+static ACCELERATION: f32 = 12.0;
+static DAMPING: f32 = 6.0;
+
+/// radians the view turns per pixel of mouse movement in fly mode.
+static LOOK_SPEED: f32 = 0.005;
+
+/// seconds a snap to one of the standard views takes.
+static SNAP_TIME: f32 = 0.4;
+
+/// keys that move the camera and the direction they move it in, in camera space.
+static MOVE_KEYS: [(VirtualKeyCode, [f32; 3]); 6] = [
+    (VirtualKeyCode::W, [0.0, 0.0, -1.0]),
+    (VirtualKeyCode::S, [0.0, 0.0, 1.0]),
+    (VirtualKeyCode::A, [-1.0, 0.0, 0.0]),
+    (VirtualKeyCode::D, [1.0, 0.0, 0.0]),
+    (VirtualKeyCode::E, [0.0, 1.0, 0.0]),
+    (VirtualKeyCode::Q, [0.0, -1.0, 0.0]),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraMode {
+    /// the left mouse button spins the camera around its target.
+    Orbit,
+    /// the left mouse button turns the camera where it stands.
+    Fly,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// parallel lines stay parallel, for measuring things up against each other.
+    Orthographic,
+}
+
+/// the standard views looking at the target along the world axes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum View {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    /// down the diagonal from the +X +Y +Z corner.
+    Isometric,
+}
+
+impl View {
+    /// camera to world rotation for the view, the camera looks down its -Z.
+    fn orientation(self) -> Quaternion<f32> {
+        // which way the camera is from the target, and which way is up on screen.
+        let (back, up) = match self {
+            View::Front => (Vector3::unit_z(), Vector3::unit_y()),
+            View::Back => (-Vector3::unit_z(), Vector3::unit_y()),
+            View::Left => (-Vector3::unit_x(), Vector3::unit_y()),
+            View::Right => (Vector3::unit_x(), Vector3::unit_y()),
+            View::Top => (Vector3::unit_y(), -Vector3::unit_z()),
+            View::Bottom => (-Vector3::unit_y(), Vector3::unit_z()),
+            View::Isometric => (Vector3::new(1.0, 1.0, 1.0).normalize(), Vector3::unit_y()),
+        };
+        let right = up.cross(back).normalize();
+        let up = back.cross(right);
+        Quaternion::from(Matrix3::from_cols(right, up, back))
+    }
+}
+
+/// cgmath builds opengl projections, vulkan's clip space has y pointing down
+/// and depth running from 0 to 1 instead of -1 to 1.
+#[rustfmt::skip]
+static VULKAN_CLIP: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, -1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// a mouse drag in progress.
+enum Drag {
+    /// arcball rotation, from where the drag started on the ball and the orientation then.
+    Rotate {
+        start: Vector3<f32>,
+        orientation: Quaternion<f32>,
+    },
+    /// sliding the target across the view plane, from the last cursor position.
+    Pan { last: (f64, f64) },
+    /// turning the view around the eye, from the last cursor position.
+    Look { last: (f64, f64) },
+}
+
+/// an eased turn towards one of the standard views.
+struct Snap {
+    from: Quaternion<f32>,
+    to: Quaternion<f32>,
+    /// seconds since the snap started.
+    elapsed: f32,
+}
+
+/// a camera circling a target point, rotated with Shoemake's arcball from
+/// "ARCBALL: A User Interface for Specifying Three-Dimensional Orientation Using a Mouse".
+/// in fly mode the target just rides along in front of the eye.
+pub struct Camera {
+    /// the point orbited around.
+    pub target: Point3<f32>,
+    /// distance from the target to the eye.
+    pub distance: f32,
+    /// camera to world rotation, the camera looks down its -Z with +Y up.
+    pub orientation: Quaternion<f32>,
+    /// vertical field of view.
+    pub fov: Rad<f32>,
+    pub near: f32,
+    pub far: f32,
+    pub mode: CameraMode,
+    pub projection: Projection,
+    drag: Option<Drag>,
+    snap: Option<Snap>,
+    /// current fly velocity in camera space.
+    velocity: Vector3<f32>,
+    /// keys currently held down.
+    held: HashSet<VirtualKeyCode>,
+}
+
+impl Camera {
+    /// a camera at `eye` looking at `target`, with world +Y up.
+    pub fn looking_from(eye: Point3<f32>, target: Point3<f32>) -> Self {
+        let back = (eye - target).normalize();
+        let right = Vector3::unit_y().cross(back).normalize();
+        let up = back.cross(right);
+
+        Camera {
+            target,
+            distance: (eye - target).magnitude(),
+            orientation: Quaternion::from(Matrix3::from_cols(right, up, back)),
+            fov: Rad(std::f32::consts::FRAC_PI_4),
+            near: 0.01,
+            far: 100.0,
+            mode: CameraMode::Orbit,
+            projection: Projection::Perspective,
+            drag: None,
+            snap: None,
+            velocity: Vector3::zero(),
+            held: HashSet::new(),
+        }
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.target
+            + self
+                .orientation
+                .rotate_vector(Vector3::unit_z() * self.distance)
+    }
+
+    /// world to camera.
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::from(self.orientation.invert()) * Matrix4::from_translation(-self.eye().to_vec())
+    }
+
+    /// camera to vulkan clip space.
+    pub fn projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective => {
+                VULKAN_CLIP * cgmath::perspective(self.fov, aspect, self.near, self.far)
+            }
+            Projection::Orthographic => {
+                // the view volume is as big as the perspective one is at the target,
+                // so switching over leaves the target the same size on screen.
+                let top = self.half_height();
+                let right = top * aspect;
+                VULKAN_CLIP * cgmath::ortho(-right, right, -top, top, self.near, self.far)
+            }
+        }
+    }
+
+    /// camera to vulkan clip space with perspective, whatever the projection.
+    /// the sky only turns it into view directions, so the depth planes are fixed
+    /// rather than borrowed from an orthographic view that may put near behind the eye.
+    pub fn sky_projection(&self, aspect: f32) -> Matrix4<f32> {
+        VULKAN_CLIP * cgmath::perspective(self.fov, aspect, 0.1, 10.0)
+    }
+
+    /// half the height of the view at the target's distance, in world units.
+    fn half_height(&self) -> f32 {
+        self.distance * (self.fov.0 / 2.0).tan()
+    }
+
+    /// start rotating from the cursor position, in physical pixels.
+    /// orbits the target or looks around depending on the mode.
+    pub fn begin_rotate(&mut self, cursor: (f64, f64), window: [u32; 2]) {
+        self.snap = None;
+        self.drag = Some(match self.mode {
+            CameraMode::Orbit => Drag::Rotate {
+                start: arcball_point(cursor, window),
+                orientation: self.orientation,
+            },
+            CameraMode::Fly => Drag::Look { last: cursor },
+        });
+    }
+
+    /// start panning from the cursor position, in physical pixels.
+    pub fn begin_pan(&mut self, cursor: (f64, f64)) {
+        self.drag = Some(Drag::Pan { last: cursor });
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    /// follow the cursor with whatever drag is in progress.
+    pub fn drag_to(&mut self, cursor: (f64, f64), window: [u32; 2]) {
+        match self.drag {
+            Some(Drag::Rotate { start, orientation }) => {
+                let end = arcball_point(cursor, window);
+                // the quaternion taking one point on the ball to the other turns through
+                // twice the angle between them, which is what makes the arcball feel right.
+                let spin = Quaternion::from_sv(start.dot(end), start.cross(end));
+                // spinning the model one way is the camera orbiting the other.
+                self.orientation = (orientation * spin.invert()).normalize();
+            }
+            Some(Drag::Pan { ref mut last }) => {
+                let (dx, dy) = (cursor.0 - last.0, cursor.1 - last.1);
+                *last = cursor;
+
+                // world units per pixel at the target's depth.
+                let scale = 2.0 * self.half_height() / window[1] as f32;
+                let offset = Vector3::new(-dx as f32 * scale, dy as f32 * scale, 0.0);
+                self.target += self.orientation.rotate_vector(offset);
+            }
+            Some(Drag::Look { ref mut last }) => {
+                let (dx, dy) = (cursor.0 - last.0, cursor.1 - last.1);
+                *last = cursor;
+
+                // yaw around the world's up so the horizon stays level, pitch around our own side.
+                let eye = self.eye();
+                let yaw = Quaternion::from_angle_y(Rad(-dx as f32 * LOOK_SPEED));
+                let pitch = Quaternion::from_angle_x(Rad(-dy as f32 * LOOK_SPEED));
+                self.orientation = (yaw * self.orientation * pitch).normalize();
+                self.target = eye
+                    - self
+                        .orientation
+                        .rotate_vector(Vector3::unit_z() * self.distance);
+            }
+            None => {}
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        };
+        self.drag = None;
+    }
+
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        };
+    }
+
+    /// turn to face the target from one of the standard views, easing there over `update`s.
+    pub fn snap_to(&mut self, view: View) {
+        let to = view.orientation();
+        // q and -q are the same rotation, take whichever is the short way round.
+        let to = if self.orientation.dot(to) < 0.0 {
+            -to
+        } else {
+            to
+        };
+        self.drag = None;
+        self.snap = Some(Snap {
+            from: self.orientation,
+            to,
+            elapsed: 0.0,
+        });
+    }
+
+    /// track a key going up or down, movement happens in `update`.
+    pub fn key(&mut self, key: VirtualKeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.held.insert(key),
+            ElementState::Released => self.held.remove(&key),
+        };
+    }
+
+    /// forget every held key, for when the window loses focus and misses the releases.
+    pub fn release_keys(&mut self) {
+        self.held.clear();
+    }
+
+    /// move with the held keys for `dt` seconds. the velocity eases towards the keys
+    /// exponentially, which comes out the same however the time is sliced in to frames.
+    /// any snap to a standard view carries on turning too.
+    pub fn update(&mut self, dt: f32) {
+        if let Some(mut snap) = self.snap.take() {
+            snap.elapsed += dt;
+            let t = (snap.elapsed / SNAP_TIME).min(1.0);
+            // smoothstep, so the turn eases in and out.
+            let t = t * t * (3.0 - 2.0 * t);
+            self.orientation = snap.from.slerp(snap.to, t).normalize();
+            if snap.elapsed < SNAP_TIME {
+                self.snap = Some(snap);
+            }
+        }
+
+        let mut input = Vector3::zero();
+        for (key, direction) in MOVE_KEYS.iter() {
+            if self.held.contains(key) {
+                input += Vector3::from(*direction);
+            }
+        }
+
+        let mut speed = FLY_SPEED;
+        if self.held.contains(&VirtualKeyCode::LShift)
+            || self.held.contains(&VirtualKeyCode::RShift)
+        {
+            speed *= FAST;
+        }
+        if self.held.contains(&VirtualKeyCode::LControl)
+            || self.held.contains(&VirtualKeyCode::RControl)
+        {
+            speed *= SLOW;
+        }
+
+        let (desired, rate) = if input.magnitude2() > 0.0 {
+            (input.normalize() * speed, ACCELERATION)
+        } else {
+            (Vector3::zero(), DAMPING)
+        };
+        self.velocity += (desired - self.velocity) * (1.0 - (-rate * dt).exp());
+        self.target += self.orientation.rotate_vector(self.velocity * dt);
+    }
+
+    /// move towards the target for positive `notches` of the mouse wheel, away for negative.
+    pub fn dolly(&mut self, notches: f32) {
+        self.distance = (self.distance * DOLLY_STEP.powf(-notches)).max(MIN_DISTANCE);
+    }
+
+    /// narrow the field of view for positive `notches`, widen it for negative.
+    pub fn zoom(&mut self, notches: f32) {
+        let fov = self.fov.0.to_degrees() * ZOOM_STEP.powf(-notches);
+        self.set_fov(fov);
+    }
+
+    /// set the vertical field of view in degrees, within sensible limits.
+    pub fn set_fov(&mut self, degrees: f32) {
+        self.fov = Rad(degrees.max(MIN_FOV).min(MAX_FOV).to_radians());
+    }
+
+    /// what the mouse wheel does, orbiting dollies in and flying zooms the lens.
+    pub fn scroll(&mut self, notches: f32) {
+        match self.mode {
+            CameraMode::Orbit => self.dolly(notches),
+            CameraMode::Fly => self.zoom(notches),
+        }
+    }
+
+    /// pull the near and far planes in as tight as they go around a sphere of `radius`
+    /// at `center`, everything worth depth testing. the skybox is drawn on the far plane
+    /// wherever it is, so it doesn't need covering.
+    /// orthographic depth is spread evenly, so there the near plane may even sit behind the eye.
+    pub fn fit_depth(&mut self, center: Point3<f32>, radius: f32) {
+        let distance = (self.eye() - center).magnitude();
+        // a little slack so the silhouette isn't clipped by rounding.
+        self.far = (distance + radius) * 1.01;
+        self.near = match self.projection {
+            Projection::Perspective => ((distance - radius) * 0.99).max(self.far / MAX_DEPTH_RATIO),
+            Projection::Orthographic => distance - radius * 1.01,
+        };
+    }
+
+    /// converts a touchpad's pixel scroll to wheel notches for `dolly`.
+    pub fn notches_from_pixels(pixels: f64) -> f32 {
+        pixels as f32 / PIXELS_PER_NOTCH
+    }
+
+    /// orbit `center` from just far enough away to see the whole of a sphere of `radius`.
+    pub fn frame(&mut self, center: Point3<f32>, radius: f32, aspect: f32) {
+        let half_vertical = self.fov.0 / 2.0;
+        self.target = center;
+        self.distance = match self.projection {
+            Projection::Perspective => {
+                let half_horizontal = (half_vertical.tan() * aspect).atan();
+                radius / half_vertical.min(half_horizontal).sin()
+            }
+            Projection::Orthographic => radius / aspect.min(1.0) / half_vertical.tan(),
+        };
+    }
+}
+
+/// project a cursor position on to the arcball, a unit sphere filling the smaller
+/// window dimension. outside the ball the point slides round its silhouette.
+fn arcball_point(cursor: (f64, f64), window: [u32; 2]) -> Vector3<f32> {
+    let (width, height) = (window[0] as f32, window[1] as f32);
+    let radius = width.min(height) / 2.0;
+    let x = (cursor.0 as f32 - width / 2.0) / radius;
+    let y = (height / 2.0 - cursor.1 as f32) / radius;
+
+    let length2 = x * x + y * y;
+    if length2 > 1.0 {
+        Vector3::new(x, y, 0.0).normalize()
+    } else {
+        Vector3::new(x, y, (1.0 - length2).sqrt())
+    }
+}
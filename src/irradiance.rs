@@ -0,0 +1,117 @@
+use crate::skybox::face_direction;
+use cgmath::{InnerSpace, Vector3};
+use image::RgbaImage;
+
+/// number of coefficients in a 3rd order (l <= 2) spherical harmonic basis.
+pub const SH_COEFFICIENTS: usize = 9;
+
+/// cosine lobe convolution constants for bands 0, 1 and 2,
+/// from Ramamoorthi and Hanrahan's "An Efficient Representation for Irradiance Environment Maps".
+static BAND_FACTORS: [f32; 3] = [
+    std::f32::consts::PI,
+    2.0 * std::f32::consts::PI / 3.0,
+    std::f32::consts::PI / 4.0,
+];
+
+/// irradiance of an environment projected in to the first nine real spherical harmonics.
+/// the coefficients are already convolved with the cosine lobe, so evaluating the basis
+/// at a normal gives irradiance directly, divide by pi for lambertian radiance.
+#[derive(Copy, Clone, Debug)]
+pub struct ShIrradiance {
+    pub coefficients: [Vector3<f32>; SH_COEFFICIENTS],
+}
+
+/// the nine real sh basis functions evaluated at a unit direction.
+fn sh_basis(dir: Vector3<f32>) -> [f32; SH_COEFFICIENTS] {
+    let (x, y, z) = (dir.x, dir.y, dir.z);
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+fn band(index: usize) -> usize {
+    match index {
+        0 => 0,
+        1..=3 => 1,
+        _ => 2,
+    }
+}
+
+/// the textures are stored in srgb, the projection must happen in linear space.
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = f32::from(c) / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ShIrradiance {
+    /// project the six faces of a cubemap in to sh irradiance.
+    /// every texel is weighted by the solid angle it subtends, so the
+    /// crowded texels in the corners of each face don't dominate the sum.
+    pub fn from_cubemap(faces: &[RgbaImage]) -> Self {
+        let mut coefficients = [Vector3::new(0.0, 0.0, 0.0); SH_COEFFICIENTS];
+        let mut total_weight = 0.0;
+
+        for (face, image) in faces.iter().enumerate().take(6) {
+            let (width, height) = image.dimensions();
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let u = 2.0 * (x as f32 + 0.5) / width as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / height as f32 - 1.0;
+
+                // differential solid angle of the texel on the unit cube.
+                let texel_area = 4.0 / (width * height) as f32;
+                let weight = texel_area / (1.0 + u * u + v * v).powf(1.5);
+
+                let dir = face_direction(face, u, v).normalize();
+                let radiance = Vector3::new(
+                    srgb_to_linear(pixel[0]),
+                    srgb_to_linear(pixel[1]),
+                    srgb_to_linear(pixel[2]),
+                );
+
+                for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(dir).iter()) {
+                    *coefficient += radiance * (basis * weight);
+                }
+                total_weight += weight;
+            }
+        }
+
+        // the weights should sum to 4 pi, renormalize to soak up the discretization error.
+        let normalization = 4.0 * std::f32::consts::PI / total_weight;
+        for (i, coefficient) in coefficients.iter_mut().enumerate() {
+            *coefficient *= normalization * BAND_FACTORS[band(i)];
+        }
+
+        ShIrradiance { coefficients }
+    }
+
+    /// coefficients padded to vec4 so they can be dropped straight in to a std140 uniform block.
+    pub fn as_uniform(&self) -> [[f32; 4]; SH_COEFFICIENTS] {
+        let mut out = [[0.0; 4]; SH_COEFFICIENTS];
+        for (o, c) in out.iter_mut().zip(self.coefficients.iter()) {
+            *o = [c.x, c.y, c.z, 0.0];
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for ShIrradiance {
+    /// one rgb triple per line, in the order L00, L1-1, L10, L11, L2-2, L2-1, L20, L21, L22.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for c in self.coefficients.iter() {
+            writeln!(f, "{:.6} {:.6} {:.6}", c.x, c.y, c.z)?;
+        }
+        Ok(())
+    }
+}
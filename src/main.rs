@@ -1,8 +1,6 @@
 /// Imports for loading files
 use std::path::Path;
 
-use std::iter;
-
 /// types from the object parser
 use tobj;
 
@@ -12,119 +10,64 @@ mod geometry;
 /// This loads textures the skybox textures and indices
 mod skybox;
 
+/// spherical harmonic projection of the skybox for diffuse lighting
+mod irradiance;
+
+/// the shaders and pipelines for each kind of draw
+mod pipelines;
+
+/// glsl compiled and reflected at runtime, with hot reloading
+mod shader;
+
+/// custom material shaders and the interface they are held to
+mod material;
+
+/// the arcball orbit camera
+mod camera;
+
 
 /// Vulkan imports, these are manifold , low level, and sinful.
-use cgmath::{Matrix3, Matrix4, Point3, Rad, Vector3};
+use camera::{Camera, View};
+use cgmath::{Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4};
+use pipelines::{FrameUniforms, PipelineRegistry, ShaderSet};
+use shader::ShaderWatcher;
+use skybox::procedural::ProceduralSky;
 use skybox::SkyBox;
+use vk::{FrameContexts, VKError};
 
-use vulkano::buffer::cpu_pool::CpuBufferPool;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::buffer::BufferUsage;
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-use vulkano::device::Device;
-use vulkano::format::Format;
-use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
-use vulkano::image::attachment::AttachmentImage;
-use vulkano::image::{Dimensions, ImmutableImage, SwapchainImage};
-use vulkano::pipeline::vertex::TwoBuffersDefinition;
-use vulkano::pipeline::viewport::Viewport;
-use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
 use vulkano::swapchain;
 use vulkano::swapchain::{AcquireError, SwapchainCreationError};
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
 
-use winit::{ElementState, KeyboardInput, VirtualKeyCode, Window};
+use winit::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode};
 
 use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
 
-use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// arg parse
 use clap::{App, Arg};
 
 mod vk;
 
-static DAMPENING: f32 = 0.005;
-
-mod vs {
-    vulkano_shaders::shader! {
-    ty: "vertex",
-        src: "
-// from the teapot example 
-#version 450
-
-layout(location = 0) in vec3 position;
-layout(location = 1) in vec3 normal;
-
-layout(location = 0) out vec3 v_normal;
-layout(location = 1) out vec4 position_out;
-
-
-layout(set = 0, binding = 0) uniform Data {
-    mat4 view;
-    mat4 proj;
-    mat4 translate;
-    mat4 scale;
-    vec3 look_dir;
-    bool is_skybox;
-} uniforms;
-
-void main() {
-    mat4 worldview = (uniforms.view * uniforms.scale);
-    v_normal = transpose(inverse(mat3(worldview))) * normal;
-    gl_Position = uniforms.proj * worldview * (uniforms.translate * vec4(position, 1.0));
-
-    if(uniforms.is_skybox){
-        position_out = vec4(position, 1.0);
-    } else {
-        position_out = gl_Position;
-    }
-
-}
-
-",
-    }
-}
+/// radians the sun moves per arrow key press in the procedural sky.
+static SUN_STEP: f32 = 0.05;
 
-// fragment shader
-mod fs {
-    vulkano_shaders::shader! {
-    ty: "fragment",
-        src: "
-// from the teapot example 
+/// radians the environment turns per key press.
+static ENV_STEP: f32 = 0.05;
 
-#version 450
+/// factor the environment intensity changes by per key press.
+static INTENSITY_STEP: f32 = 1.1;
 
-layout(location = 0) in vec3 v_normal;
-layout(location = 1) in vec4 frag_position;
+/// the most samples the skybox is filtered with at glancing angles.
+static MAX_ANISOTROPY: f32 = 16.0;
 
-layout(location = 0) out vec4 f_color;
-
-layout(set = 1, binding = 0) uniform samplerCube cubetex;
-
-layout(set = 0, binding = 0) uniform Data {
-    mat4 view;
-    mat4 proj;
-    mat4 translate;
-    mat4 scale;
-    vec3 look_dir; // the direction we are looking
-    bool is_skybox;
-} uniforms;
-
-const vec3 camera_position = vec3(0.3,0.3,1.0);
-
-void main() {
-    //float brightness = dot(normalize(v_normal), normalize(LIGHT));
-    if (uniforms.is_skybox) {
-        f_color = texture(cubetex, vec3(frag_position));
-    } else {
-        f_color = texture(cubetex, vec3(normalize(reflect(normalize(uniforms.look_dir - vec3(frag_position)), v_normal))));
-    }
-}
-        ",
-    }
-}
+/// how long to wait between checks while the window is minimized.
+static IDLE_SLEEP: Duration = Duration::from_millis(50);
 
 fn main() {
     // arg parsing, fails the program without input file
@@ -134,30 +77,210 @@ fn main() {
         .about("cross platform Obj loader in rust using vulkan bindings")
         .arg(
             Arg::with_name("input")
-                .required(true)
+                .required_unless("list_devices")
                 .short("i")
                 .long("input")
                 .value_name("fname")
                 .help("the name of the input wavefront .obj file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("skybox")
+                .long("skybox")
+                .value_name("fname")
+                .help("a dds or ktx2 cubemap, a cross, 6x1 or 1x6 strip image, or \"procedural\"")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("turbidity")
+                .long("turbidity")
+                .value_name("amount")
+                .help("haziness of the procedural sky, 2 is clear and 10 is murky")
+                .default_value("3.0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("env_yaw")
+                .long("env-yaw")
+                .value_name("degrees")
+                .help("rotation of the environment around the vertical axis")
+                .default_value("0.0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("env_pitch")
+                .long("env-pitch")
+                .value_name("degrees")
+                .help("tilt of the environment towards or away from the viewer")
+                .default_value("0.0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("env_intensity")
+                .long("env-intensity")
+                .value_name("amount")
+                .help("brightness multiplier for the skybox and everything it lights")
+                .default_value("1.0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("diffuse")
+                .long("diffuse")
+                .value_name("amount")
+                .help("how much diffuse skybox irradiance to mix in to the reflection, 0 to 1")
+                .default_value("0.0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fov")
+                .long("fov")
+                .value_name("degrees")
+                .help("vertical field of view, the mouse wheel changes it in fly mode")
+                .default_value("45.0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("shader_dir")
+                .long("shader-dir")
+                .value_name("dir")
+                .help("load the .vert and .frag files from here instead, reloading them on change")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("shader")
+                .long("shader")
+                .value_name("fname")
+                .help("a custom material fragment shader for the model, see the readme")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("material_params")
+                .long("material-params")
+                .value_name("x,y,z,w")
+                .help("four numbers handed to the material as material.params")
+                .default_value("0,0,0,0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("list_devices")
+                .long("list-devices")
+                .help("print the vulkan devices, their memory and queue families, then exit"),
+        )
+        .arg(
+            Arg::with_name("device")
+                .long("device")
+                .value_name("index|name")
+                .help("the device to render on, by index or part of its name from --list-devices")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("present_mode")
+                .long("present-mode")
+                .value_name("mode")
+                .help("fifo and relaxed wait for vsync, mailbox and immediate don't. v toggles it")
+                .possible_values(&["fifo", "mailbox", "immediate", "relaxed"])
+                .default_value("fifo")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("validate_vulkan")
+                .long("validate-vulkan")
+                .help("turn on the vulkan validation layers, printing what they find to stderr"),
+        )
+        .arg(
+            Arg::with_name("validation_level")
+                .long("validation-level")
+                .value_name("severity")
+                .help("the least severe validation message to print with --validate-vulkan")
+                .possible_values(&["error", "warning", "performance", "info", "debug"])
+                .default_value("warning")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("frame_times")
+                .long("frame-times")
+                .help("print the average frame time every second, best with vsync off"),
+        )
+        .arg(
+            Arg::with_name("print_sh")
+                .long("print-sh")
+                .help("print the skybox's spherical harmonic irradiance coefficients"),
+        )
         .get_matches();
 
+    if matches.is_present("list_devices") {
+        if let Err(e) = vk::list_devices() {
+            exit_with(&e);
+        }
+        return;
+    }
+
+    let diffuse: f32 = matches
+        .value_of("diffuse")
+        .unwrap()
+        .parse()
+        .expect("diffuse must be a number");
+
+    let material_params = matches
+        .value_of("material_params")
+        .unwrap()
+        .split(',')
+        .map(|value| value.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+    let material_params = match material_params {
+        Ok(ref values) if values.len() == 4 => [values[0], values[1], values[2], values[3]],
+        _ => {
+            eprintln!("error: --material-params takes exactly four numbers, like 1,0.5,0,2");
+            std::process::exit(1);
+        }
+    };
+
+    // environment controls, adjustable at runtime with j/l (yaw), i/k (pitch) and [/] (intensity).
+    let parse_arg = |name: &str| -> f32 {
+        matches
+            .value_of(name)
+            .unwrap()
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a number", name))
+    };
+    let mut env_yaw = parse_arg("env_yaw").to_radians();
+    let mut env_pitch = parse_arg("env_pitch").to_radians();
+    let mut env_intensity = parse_arg("env_intensity");
+
     // parse object with tiny object loader
     let obj_file = tobj::load_obj(&Path::new(matches.value_of("input").unwrap()));
     assert!(obj_file.is_ok());
 
     let (geom, _mats) = obj_file.unwrap();
 
-    let skybox = SkyBox::new();
+    // the procedural sky is regenerated whenever the sun moves,
+    // any other skybox is fixed for the whole run.
+    let mut procedural_sky = match matches.value_of("skybox") {
+        Some("procedural") => Some(ProceduralSky {
+            turbidity: matches
+                .value_of("turbidity")
+                .unwrap()
+                .parse()
+                .expect("turbidity must be a number"),
+            ..ProceduralSky::default()
+        }),
+        _ => None,
+    };
+    let mut sky_changed = false;
+
+    let mut skybox = match (matches.value_of("skybox"), &procedural_sky) {
+        (_, Some(sky)) => SkyBox::procedural(sky),
+        (Some(fname), None) => {
+            SkyBox::from_file(&Path::new(fname)).expect("could not load the skybox")
+        }
+        (None, None) => SkyBox::new(),
+    };
 
-    let image_data = skybox.textures.iter().cloned().map(|x| x.into_raw()).fold(
-        Vec::new(),
-        |mut agg: Vec<u8>, mut x| {
-            agg.append(&mut x);
-            agg
-        },
-    );
+    // project the skybox up front, only the procedural sky ever changes.
+    let mut sh = irradiance::ShIrradiance::from_cubemap(&skybox.textures);
+    if matches.is_present("print_sh") {
+        print!("{}", sh);
+    }
 
     // break the positions up into groups of three
     let model_verts: Vec<geometry::Vertex> = geom[0]
@@ -174,125 +297,111 @@ fn main() {
     let (model_normals, extent) =
         geometry::norms_from_verts_and_index(&model_verts, &geom[0].mesh.indices);
 
-    let mut vk_state: vk::VKState = vk::VKState::vk_init().expect("initialization failed \n");
-    let window = vk_state.surface.window();
-
-    let (texture, tex_future) = {
-        ImmutableImage::from_iter(
-            image_data.iter().cloned(),
-            Dimensions::Cubemap { size: 512 },
-            Format::R8G8B8A8Srgb,
-            vk_state.queue.clone(),
+    let validation = if matches.is_present("validate_vulkan") {
+        Some(
+            matches
+                .value_of("validation_level")
+                .unwrap()
+                .parse()
+                .unwrap(),
         )
-        .unwrap()
+    } else {
+        None
     };
+    let present_mode = vk::parse_present_mode(matches.value_of("present_mode").unwrap()).unwrap();
+    let mut vk_state =
+        match vk::VKState::vk_init(matches.value_of("device"), validation, present_mode) {
+            Ok(vk_state) => vk_state,
+            Err(e) => exit_with(&e),
+        };
+    let window = vk_state.surface.window();
 
+    let (mut texture, tex_future) = skybox
+        .upload(vk_state.queue.clone())
+        .expect("could not upload the skybox");
+
+    // only filter anisotropically if the device was created with it.
+    let anisotropy = if vk_state.device.enabled_features().sampler_anisotropy {
+        vk_state
+            .device
+            .physical_device()
+            .limits()
+            .max_sampler_anisotropy()
+            .min(MAX_ANISOTROPY)
+    } else {
+        1.0
+    };
     let sampler = Sampler::new(
         vk_state.device.clone(),
         Filter::Linear,
         Filter::Linear,
-        MipmapMode::Nearest,
+        MipmapMode::Linear,
         SamplerAddressMode::Repeat,
         SamplerAddressMode::Repeat,
         SamplerAddressMode::Repeat,
         0.0,
-        1.0,
-        0.0,
+        anisotropy,
         0.0,
+        (skybox.mip_levels() - 1) as f32,
     )
     .unwrap();
 
-    let vertex_buffer = CpuAccessibleBuffer::from_iter(
-        vk_state.device.clone(),
-        BufferUsage::all(),
-        model_verts.iter().cloned(),
-    )
-    .unwrap();
-
-    let normals_buffer = CpuAccessibleBuffer::from_iter(
-        vk_state.device.clone(),
-        BufferUsage::all(),
-        model_normals.iter().cloned(),
-    )
-    .unwrap();
-
-    let index_buffer = CpuAccessibleBuffer::from_iter(
-        vk_state.device.clone(),
-        BufferUsage::all(),
-        geom[0].mesh.indices.iter().cloned(),
-    )
-    .unwrap();
+    // the geometry never changes, so it lives in device local memory the cpu can't see.
+    let (vertex_buffer, vertex_future) =
+        vk_state.upload(model_verts.iter().cloned(), BufferUsage::vertex_buffer());
+    let (normals_buffer, normals_future) =
+        vk_state.upload(model_normals.iter().cloned(), BufferUsage::vertex_buffer());
+    let (index_buffer, index_future) =
+        vk_state.upload(geom[0].mesh.indices.iter().cloned(), BufferUsage::index_buffer());
+    let (skybox_vertices_buffer, skybox_vertices_future) =
+        vk_state.upload(skybox.vertices.iter().cloned(), BufferUsage::vertex_buffer());
+    let (skybox_indices_buffer, skybox_indices_future) =
+        vk_state.upload(skybox.indices.iter().cloned(), BufferUsage::index_buffer());
+
+    // the uniforms for each frame in flight, so one can be written while another is drawn.
+    let device = vk_state.device.clone();
+    let mut frames = FrameContexts::new(device.clone(), || FrameUniforms::new(&device));
+    // NOTE : had to join the futures for correctness.
+    // the first frame waits for every upload.
+    frames.wait_for(tex_future);
+    for future in vec![
+        vertex_future,
+        normals_future,
+        index_future,
+        skybox_vertices_future,
+        skybox_indices_future,
+    ] {
+        frames.wait_for(future);
+    }
 
-    let skybox_vertices_buffer = CpuAccessibleBuffer::from_iter(
-        vk_state.device.clone(),
-        BufferUsage::all(),
-        skybox.vertices.iter().cloned(),
-    )
-    .unwrap();
+    let shader_dir = matches.value_of("shader_dir").map(Path::new);
+    let material = matches.value_of("shader").map(Path::new);
+    // the render pass, pipelines and framebuffers, rebuilt with the swapchain.
+    let loaded = ShaderSet::load(&vk_state.device, shader_dir, material).and_then(|shaders| {
+        PipelineRegistry::new(vk_state.device.clone(), &vk_state.images, shaders)
+    });
+    let mut pipelines = match loaded {
+        Ok(pipelines) => pipelines,
+        Err(e) => {
+            eprintln!("error: could not load the shaders\n{}", e);
+            std::process::exit(1);
+        }
+    };
+    // only shaders loaded from disk are watched, the built in ones never change.
+    let shader_watcher =
+        ShaderWatcher::new(pipelines.shaders.paths()).expect("could not watch the shaders");
 
-    let skybox_indices_buffer = CpuAccessibleBuffer::from_iter(
-        vk_state.device.clone(),
-        BufferUsage::all(),
-        skybox.indices.iter().cloned(),
-    )
-    .unwrap();
+    // the environment the model reflects, which only changes with the sky or a layout.
+    let mut texture_set = pipelines.environment_set(&texture, &sampler);
+    let mut texture_set_version = pipelines.layout_version;
 
-    let uniform_buffer =
-        CpuBufferPool::<vs::ty::Data>::new(vk_state.device.clone(), BufferUsage::all());
-
-    // compile frag and vertex shaders here
-    let vs = vs::Shader::load(vk_state.device.clone()).unwrap();
-    let fs = fs::Shader::load(vk_state.device.clone()).unwrap();
-
-    let render_pass = Arc::new(
-        vulkano::single_pass_renderpass!(vk_state.device.clone(),
-            attachments: {
-                color: {
-                    load: Clear,
-                    store: Store,
-                    format: vk_state.swapchain.format(),
-                    samples: 1,
-                },
-                depth: {
-                    load: Clear,
-                    store: DontCare,
-                    format: Format::D16Unorm,
-                    samples: 1,
-                }
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {depth}
-            }
-        )
-        .unwrap(),
-    );
+    let mut recreate_swapchain = false;
 
-    // todo: figure out what the hell these are for
-    // they are an abstract part of this boilerplate
-    let (mut pipeline, mut framebuffers) = window_size_dependent_setup(
-        vk_state.device.clone(),
-        &vs,
-        &fs,
-        &vk_state.images,
-        render_pass.clone(),
-    );
+    // the last cursor position in physical pixels, winit only reports it when it moves.
+    let mut cursor = (0.0, 0.0);
 
-    let mut recreate_swapchain = false;
-    // NOTE : had to join the futures for correctness.
-    let mut previous_frame =
-        Box::new(tex_future.join(sync::now(vk_state.device.clone()))) as Box<GpuFuture>;
-
-    // these are used to rotate the world projection
-    // modifed in the mouse events after each frame,
-    // used in the uniform sub buffer each frame
-    let mut y_delta: f32 = 0.0;
-    let mut x_delta: f32 = 0.0;
-    let mut mouse_state: winit::ElementState = winit::ElementState::Released;
-
-    let mut camera_pos = Point3::new(0.3, 0.3, 1.0);
-    let mut camera_velocity = Vector3::new(0.0, 0.0, 0.0);
-    let camera_dir = Vector3::new(-0.3, -0.3, -1.0);
+    let mut camera = Camera::looking_from(Point3::new(0.3, 0.3, 1.0), Point3::new(0.0, 0.0, 0.0));
+    camera.set_fov(parse_arg("fov"));
     // translation matrix
     // which moves the model to the origin.
     let offset = extent[0] + extent[1];
@@ -309,116 +418,287 @@ fn main() {
     // scale is whatever reduces the largest
     // dimension to 0.3, because that is a nice size .
     let scale = 0.3 / max;
+    // after translating and scaling the model sits at the origin inside this radius.
+    let model_radius = 0.5 * scale * ((l - i).powi(2) + (m - j).powi(2) + (n - k).powi(2)).sqrt();
 
-    // shoemake arcball camera set up
-    let aspect_ratio = vk_state.dimensions[0] as f32 / vk_state.dimensions[1] as f32;
-    let proj = cgmath::perspective(Rad(std::f32::consts::FRAC_PI_4), aspect_ratio, 0.01, 100.0);
+    // material shaders get the time since startup for animating.
+    let start_time = Instant::now();
 
-    loop {
-        let rotation =
-            { Matrix3::from_angle_y(Rad(y_delta)) * Matrix3::from_angle_x(Rad(x_delta)) };
+    let mut last_frame = Instant::now();
+    // a second's worth of frame times, averaged and printed with --frame-times.
+    let print_frame_times = matches.is_present("frame_times");
+    let mut frame_times = Vec::new();
 
-        let dir = rotation * camera_dir;
-        camera_pos += camera_velocity;
-        let camera: Matrix4<f32> =
-            Matrix4::look_at_dir(camera_pos, dir, Vector3::new(0.0, -1.0, 0.0));
+    loop {
+        let mut done = false;
+        let mut toggle_vsync = false;
+        let hidpi_factor = window.get_hidpi_factor();
+        let dimensions = vk_state.dimensions;
 
-        previous_frame.cleanup_finished();
+        // the loop that parses user events
+        // very simple and high level! I love it.
+        vk_state.events_loop.poll_events(|ev| match ev {
+            winit::Event::WindowEvent {
+                event: winit::WindowEvent::CloseRequested,
+                ..
+            } => done = true,
+            winit::Event::WindowEvent {
+                event: winit::WindowEvent::Resized(_),
+                ..
+            } => recreate_swapchain = true,
+            // key releases go to whichever window has focus, so drop them all rather than
+            // drifting on forever after alt tabbing away.
+            winit::Event::WindowEvent {
+                event: winit::WindowEvent::Focused(false),
+                ..
+            } => camera.release_keys(),
+            // left drag orbits or looks, middle drag pans and the wheel dollies or zooms.
+            winit::Event::WindowEvent {
+                event: winit::WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                cursor = position.to_physical(hidpi_factor).into();
+                camera.drag_to(cursor, dimensions);
+            }
+            winit::Event::WindowEvent {
+                event: winit::WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => match (state, button) {
+                (ElementState::Pressed, MouseButton::Left) => {
+                    camera.begin_rotate(cursor, dimensions)
+                }
+                (ElementState::Pressed, MouseButton::Middle) => camera.begin_pan(cursor),
+                (ElementState::Released, _) => camera.end_drag(),
+                _ => {}
+            },
+            winit::Event::WindowEvent {
+                event: winit::WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => match delta {
+                MouseScrollDelta::LineDelta(_, y) => camera.scroll(y),
+                MouseScrollDelta::PixelDelta(position) => {
+                    camera.scroll(Camera::notches_from_pixels(position.y))
+                }
+            },
 
-        if recreate_swapchain {
-            vk_state.dimensions = if let Some(dimensions) = window.get_inner_size() {
-                let dimensions: (u32, u32) =
-                    dimensions.to_physical(window.get_hidpi_factor()).into();
-                [dimensions.0, dimensions.1]
-            } else {
-                return;
-            };
+            winit::Event::WindowEvent {
+                event:
+                    winit::WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: key,
+                                state,
+                                ..
+                            },
+                        ..
+                    },
 
-            let (new_swapchain, new_images) = match vk_state
-                .swapchain
-                .recreate_with_dimension(vk_state.dimensions)
-            {
-                Ok(r) => r,
-                Err(SwapchainCreationError::UnsupportedDimensions) => continue,
-                Err(err) => panic!("{:?}", err),
-            };
-            vk_state.swapchain = new_swapchain;
-
-            let (new_pipeline, new_framebuffers) = window_size_dependent_setup(
-                vk_state.device.clone(),
-                &vs,
-                &fs,
-                &new_images,
-                render_pass.clone(),
-            );
-            pipeline = new_pipeline;
-            framebuffers = new_framebuffers;
+                ..
+            } => {
+                // wasd, q and e move the camera while held, shift and control change speed.
+                if let Some(key) = key {
+                    camera.key(key, state);
+                }
+                if state == ElementState::Pressed {
+                    match key {
+                        Some(VirtualKeyCode::Tab) => {
+                            camera.toggle_mode();
+                            println!("{:?} camera", camera.mode);
+                        }
+                        Some(VirtualKeyCode::V) => toggle_vsync = true,
+                        Some(VirtualKeyCode::Z) => {
+                            let wireframe = pipelines.toggle_wireframe();
+                            println!("{}", if wireframe { "wireframe" } else { "filled" });
+                        }
+                        Some(VirtualKeyCode::O) => {
+                            camera.toggle_projection();
+                            println!("{:?} projection", camera.projection);
+                        }
+                        Some(VirtualKeyCode::Key1) => camera.snap_to(View::Front),
+                        Some(VirtualKeyCode::Key2) => camera.snap_to(View::Back),
+                        Some(VirtualKeyCode::Key3) => camera.snap_to(View::Left),
+                        Some(VirtualKeyCode::Key4) => camera.snap_to(View::Right),
+                        Some(VirtualKeyCode::Key5) => camera.snap_to(View::Top),
+                        Some(VirtualKeyCode::Key6) => camera.snap_to(View::Bottom),
+                        Some(VirtualKeyCode::Key7) => camera.snap_to(View::Isometric),
+                        // the arrow keys move the sun of the procedural sky
+                        Some(VirtualKeyCode::Left)
+                        | Some(VirtualKeyCode::Right)
+                        | Some(VirtualKeyCode::Up)
+                        | Some(VirtualKeyCode::Down) => {
+                            if let Some(ref mut sky) = procedural_sky {
+                                match key {
+                                    Some(VirtualKeyCode::Left) => sky.rotate_sun(-SUN_STEP, 0.0),
+                                    Some(VirtualKeyCode::Right) => sky.rotate_sun(SUN_STEP, 0.0),
+                                    Some(VirtualKeyCode::Up) => sky.rotate_sun(0.0, SUN_STEP),
+                                    _ => sky.rotate_sun(0.0, -SUN_STEP),
+                                }
+                                sky_changed = true;
+                            }
+                        }
+                        Some(VirtualKeyCode::F) => camera.frame(
+                            Point3::new(0.0, 0.0, 0.0),
+                            model_radius,
+                            dimensions[0] as f32 / dimensions[1] as f32,
+                        ),
+                        Some(VirtualKeyCode::J) => env_yaw -= ENV_STEP,
+                        Some(VirtualKeyCode::L) => env_yaw += ENV_STEP,
+                        Some(VirtualKeyCode::I) => env_pitch -= ENV_STEP,
+                        Some(VirtualKeyCode::K) => env_pitch += ENV_STEP,
+                        Some(VirtualKeyCode::LBracket) => env_intensity /= INTENSITY_STEP,
+                        Some(VirtualKeyCode::RBracket) => env_intensity *= INTENSITY_STEP,
+                        _ => {}
+                    }
+                }
+            }
+            _ => (),
+        });
+        if done {
+            return;
+        }
+        // the events loop is borrowed from vk_state while polling, so this waits till after.
+        if toggle_vsync {
+            match vk_state.toggle_vsync() {
+                Ok(()) => {
+                    println!("{:?} presentation", vk_state.present_mode);
+                    recreate_swapchain = true;
+                }
+                // the present mode is only changed once the capabilities are in hand.
+                Err(e) => println!("{}, keeping {:?} presentation", e, vk_state.present_mode),
+            }
+        }
 
-            recreate_swapchain = false;
+        // seconds since the last frame, capped so a stall doesn't fling the camera away.
+        let now = Instant::now();
+        let dt = now.duration_since(last_frame);
+        last_frame = now;
+        let dt = dt.as_secs() as f32 + dt.subsec_nanos() as f32 * 1e-9;
+        if print_frame_times {
+            frame_times.push(dt);
+            if frame_times.iter().sum::<f32>() >= 1.0 {
+                let mean = frame_times.iter().sum::<f32>() / frame_times.len() as f32;
+                println!("{:.2} ms per frame, {:.0} fps", mean * 1000.0, 1.0 / mean);
+                frame_times.clear();
+            }
+        }
+        let dt = dt.min(0.1);
+        camera.update(dt);
+        camera.fit_depth(Point3::new(0.0, 0.0, 0.0), model_radius);
+
+        if sky_changed {
+            if let Some(ref sky) = procedural_sky {
+                skybox = SkyBox::procedural(sky);
+                sh = irradiance::ShIrradiance::from_cubemap(&skybox.textures);
+                match skybox.upload(vk_state.queue.clone()) {
+                    Ok((new_texture, upload_future)) => {
+                        texture = new_texture;
+                        frames.wait_for(upload_future);
+                        // every set sampling the old texture has to be rebuilt.
+                        texture_set = pipelines.environment_set(&texture, &sampler);
+                        for uniforms in frames.resources_mut() {
+                            uniforms.invalidate();
+                        }
+                    }
+                    // keep drawing the old sky.
+                    Err(e) => println!("could not upload the sky: {:?}", e),
+                }
+            }
+            sky_changed = false;
         }
-        // !! important !! this is what gets fed to our friends
-        // the vertex and frag shaders.
-        let uniform_buffer_subbuffer = {
 
-            let scale = Matrix4::from_scale(scale);
+        let changed_shaders = shader_watcher.changed();
+        if !changed_shaders.is_empty() {
+            pipelines.reload(&changed_shaders);
+        }
+        if pipelines.layout_version != texture_set_version {
+            texture_set = pipelines.environment_set(&texture, &sampler);
+            texture_set_version = pipelines.layout_version;
+        }
 
-            let uniform_data = vs::ty::Data {
-                view: camera.into(),
-                proj: proj.into(),
-                translate: translate.into(),
-                look_dir: camera_pos.into(),
-                scale: scale.into(),
-                is_skybox: 0,
+        if recreate_swapchain {
+            // a minimized window has no area, or on some platforms no size at all.
+            // there is nothing to draw in to until it comes back, so idle until it resizes.
+            let dimensions = window
+                .get_inner_size()
+                .map(|size| size.to_physical(window.get_hidpi_factor()).into())
+                .filter(|&(width, height): &(u32, u32)| width > 0 && height > 0);
+            let dimensions = match dimensions {
+                Some((width, height)) => [width, height],
+                None => {
+                    thread::sleep(IDLE_SLEEP);
+                    continue;
+                }
             };
 
-            uniform_buffer.next(uniform_data).unwrap()
-        };
-        // todo remove duplicate code;
-        let skybox_subbuffer = {
-
-            let scale = Matrix4::from_scale(4.0);
-
-            // translate to center
-            let translate = Matrix4::from_translation(Vector3::from((0.0, 0.0, 0.0)));
+            match vk_state.recreate_swapchain(dimensions) {
+                Ok(()) => {}
+                // the window changed size again under us, try again next time round.
+                Err(VKError::VKSwapchainError(SwapchainCreationError::UnsupportedDimensions)) => {
+                    thread::sleep(IDLE_SLEEP);
+                    continue;
+                }
+                Err(err) => panic!("{}", err),
+            }
 
-            let uniform_data = vs::ty::Data {
-                view: camera.into(),
-                proj: proj.into(),
-                translate: translate.into(),
-                look_dir: camera_pos.into(),
-                scale: scale.into(),
-                is_skybox: 1,
-            };
+            // the old framebuffers hold the swapchain images that were just replaced,
+            // so there's nothing left to draw with.
+            if let Err(e) = pipelines.rebuild(&vk_state.images) {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
 
-            uniform_buffer.next(uniform_data).unwrap()
-        };
+            recreate_swapchain = false;
+        }
 
+        // the projection follows the swapchain, so it stays undistorted through resizes.
+        let aspect_ratio = vk_state.dimensions[0] as f32 / vk_state.dimensions[1] as f32;
+        let view = camera.view();
+        let proj = camera.projection_matrix(aspect_ratio);
 
-        let texture_set = Arc::new(
-            PersistentDescriptorSet::start(pipeline.clone(), 1)
-                .add_sampled_image(texture.clone(), sampler.clone())
-                .unwrap()
-                .build()
-                .unwrap(),
-        );
+        let env_rotation =
+            Matrix4::from_angle_x(Rad(env_pitch)) * Matrix4::from_angle_y(Rad(env_yaw));
 
-        let set0 = Arc::new(
-            PersistentDescriptorSet::start(pipeline.clone(), 0)
-                .add_buffer(uniform_buffer_subbuffer.clone())
-                .unwrap()
-                .build()
-                .unwrap(),
-        );
+        // !! important !! this is what gets fed to our friends
+        // the vertex and frag shaders.
+        let frame_data = {
 
-        let skybox_geometry_set = Arc::new(
-            PersistentDescriptorSet::start(pipeline.clone(), 0)
-                .add_buffer(skybox_subbuffer.clone())
-                .unwrap()
-                .build()
-                .unwrap(),
-        );
+            let model = Matrix4::from_scale(scale) * translate;
+            let elapsed = start_time.elapsed();
 
+            pipelines::model_fs::ty::Frame {
+                view: view.into(),
+                proj: proj.into(),
+                model: model.into(),
+                sh: sh.as_uniform(),
+                env_rotation: env_rotation.into(),
+                camera_position: camera.eye().into(),
+                time: elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9,
+                env_intensity,
+                encode_srgb: vk_state.encode_srgb as u32,
+            }
+        };
+        let material_data = pipelines::model_fs::ty::Material {
+            params: material_params,
+            diffuse,
+        };
+        // the sky is infinitely far away, so it only sees the camera's rotation.
+        let skybox_data = {
+            let mut sky_view = view;
+            sky_view.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+
+            // a parallel projection would see the same one direction everywhere,
+            // so the sky always keeps its perspective.
+            let sky_proj = camera.sky_projection(aspect_ratio);
+            pipelines::sky_vs::ty::Data {
+                inv_view_proj: (sky_proj * sky_view).invert().unwrap().into(),
+                env_rotation: env_rotation.into(),
+                env_intensity,
+                encode_srgb: vk_state.encode_srgb as u32,
+            }
+        };
 
+        // acquire before begin, skipping the frame after begin has taken the pending
+        // uploads would drop their future with nothing waiting on it.
         let (image_num, acquire_future) =
             match swapchain::acquire_next_image(vk_state.swapchain.clone(), None) {
                 Ok(r) => r,
@@ -429,37 +709,42 @@ fn main() {
                 Err(err) => panic!("{:?}", err),
             };
 
+        // waits for the gpu to finish the last frame that used these buffers.
+        let (uniforms, start) = frames.begin();
+        uniforms.write(frame_data, material_data, skybox_data);
+        let (set0, skybox_set) = {
+            let sets = uniforms.sets(&pipelines, &texture, &sampler);
+            (sets.model.clone(), sets.skybox.clone())
+        };
+
         let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
             vk_state.device.clone(),
             vk_state.queue.family(),
         )
         .unwrap()
         .begin_render_pass(
-            framebuffers[image_num].clone(),
+            pipelines.framebuffers[image_num].clone(),
             false,
             vec![[0.0, 0.0, 0.0, 1.0].into(), 1f32.into()],
         )
         .unwrap()
-        //  draw skybox
+        // Draw the model
         .draw_indexed(
-            pipeline.clone(),
+            pipelines.model.clone(),
             &DynamicState::none(),
-            vec![
-                skybox_vertices_buffer.clone(),
-                skybox_vertices_buffer.clone(),
-            ],
-            skybox_indices_buffer.clone(),
-            (skybox_geometry_set.clone(), texture_set.clone()),
+            vec![vertex_buffer.clone(), normals_buffer.clone()],
+            index_buffer.clone(),
+            (set0.clone(), texture_set.clone()),
             (),
         )
         .unwrap()
-        // Draw the model
+        //  draw skybox last, the depth test skips every pixel the model covered
         .draw_indexed(
-            pipeline.clone(),
+            pipelines.skybox.clone(),
             &DynamicState::none(),
-            vec![vertex_buffer.clone(), normals_buffer.clone()],
-            index_buffer.clone(),
-            (set0.clone(), texture_set.clone()),
+            vec![skybox_vertices_buffer.clone()],
+            skybox_indices_buffer.clone(),
+            skybox_set.clone(),
             (),
         )
         .unwrap()
@@ -468,163 +753,36 @@ fn main() {
         .build()
         .unwrap();
 
-        let future = previous_frame
-            .join(acquire_future)
-            .then_execute(vk_state.queue.clone(), command_buffer)
-            .unwrap()
-            .then_swapchain_present(
-                vk_state.queue.clone(),
-                vk_state.swapchain.clone(),
-                image_num,
-            )
-            .then_signal_fence_and_flush();
-
-        match future {
-            Ok(future) => {
-                previous_frame = Box::new(future) as Box<_>;
-            }
+        let future = Box::new(
+            start
+                .join(acquire_future)
+                .then_execute(vk_state.queue.clone(), command_buffer)
+                .unwrap()
+                .then_swapchain_present(
+                    vk_state.queue.clone(),
+                    vk_state.swapchain.clone(),
+                    image_num,
+                ),
+        ) as Box<GpuFuture>;
+
+        match future.then_signal_fence_and_flush() {
+            Ok(fence) => frames.end(Some(fence)),
             Err(sync::FlushError::OutOfDate) => {
                 recreate_swapchain = true;
-                previous_frame = Box::new(sync::now(vk_state.device.clone())) as Box<_>;
+                frames.end(None);
             }
             Err(e) => {
                 println!("{:?}", e);
-                previous_frame = Box::new(sync::now(vk_state.device.clone())) as Box<_>;
+                frames.end(None);
             }
         }
-
-        let mut done = false;
-
-        // the loop that parses user events
-        // very simple and high level! I love it.
-        vk_state.events_loop.poll_events(|ev| match ev {
-            winit::Event::WindowEvent {
-                event: winit::WindowEvent::CloseRequested,
-                ..
-            } => done = true,
-            winit::Event::WindowEvent {
-                event: winit::WindowEvent::Resized(_),
-                ..
-            } => recreate_swapchain = true,
-            winit::Event::WindowEvent {
-                event: winit::WindowEvent::MouseInput { state: s, .. },
-                ..
-            } => (mouse_state = s),
-            winit::Event::DeviceEvent {
-                event: winit::DeviceEvent::MouseMotion { delta: (x, y), .. },
-                ..
-            } => {
-                if mouse_state == ElementState::Released {
-                    return;
-                }
-                x_delta += DAMPENING * y as f32;
-                y_delta -= DAMPENING * x as f32;
-            }
-
-            winit::Event::WindowEvent {
-                event:
-                    winit::WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                virtual_keycode: key,
-                                state,
-                                ..
-                            },
-                        ..
-                    },
-
-                ..
-            } => {
-                if state == ElementState::Pressed {
-                    match key {
-                        Some(VirtualKeyCode::W) => {
-                            camera_velocity.z -= 0.02;
-                        }
-                        Some(VirtualKeyCode::A) => {
-                            camera_velocity.x += 0.02;
-                        }
-                        Some(VirtualKeyCode::S) => {
-                            camera_velocity.z += 0.02;
-                        }
-                        Some(VirtualKeyCode::D) => {
-                            camera_velocity.x -= 0.02;
-                        }
-                        Some(VirtualKeyCode::Space) => {
-                            camera_velocity.y += 0.02;
-                        }
-                        Some(VirtualKeyCode::LShift) => {
-                            camera_velocity.y -= 0.02;
-                        }
-                        _ => {}
-                    }
-                } else {
-                    camera_velocity = Vector3::new(0.0, 0.0, 0.0);
-                }
-            }
-            _ => (),
-        });
-        if done {
-            return;
-        }
     }
 
 }
 
-/// A window resizing function , nithing to do with the loop.
-/// I did not write this shit it is beyond me. It also
-/// SEG FAULTS HARDCORE...
-/// TODO: make this never get called.
-fn window_size_dependent_setup(
-    device: Arc<Device>,
-    vs: &vs::Shader,
-    fs: &fs::Shader,
-    images: &[Arc<SwapchainImage<Window>>],
-    render_pass: Arc<RenderPassAbstract + Send + Sync>,
-) -> (
-    Arc<GraphicsPipelineAbstract + Send + Sync>,
-    Vec<Arc<FramebufferAbstract + Send + Sync>>,
-) {
-    let dimensions = images[0].dimensions();
-
-    let depth_buffer =
-        AttachmentImage::transient(device.clone(), dimensions, Format::D16Unorm).unwrap();
-
-    let framebuffers = images
-        .iter()
-        .map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .add(depth_buffer.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<FramebufferAbstract + Send + Sync>
-        })
-        .collect::<Vec<_>>();
-
-    // In the triangle example we use a dynamic viewport, as its a simple example.
-    // However in the teapot example, we recreate the pipelines with a hardcoded viewport instead.
-    // This allows the driver to optimize things, at the cost of slower window resizes.
-    // https://computergraphics.stackexchange.com/questions/5742/vulkan-best-way-of-updating-pipeline-viewport
-    let pipeline = Arc::new(
-        GraphicsPipeline::start()
-            .vertex_input(TwoBuffersDefinition::<geometry::Vertex, geometry::Normal>::new())
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            .viewports_dynamic_scissors_irrelevant(1)
-            .viewports(iter::once(Viewport {
-                origin: [0.0, 0.0],
-                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-                depth_range: 0.0..1.0,
-            }))
-            .fragment_shader(fs.main_entry_point(), ())
-            .depth_stencil_simple_depth()
-            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-            .build(device.clone())
-            .unwrap(),
-    );
-
-    (pipeline, framebuffers)
+/// explain why vulkan couldn't start and what might fix it, rather than panicking.
+fn exit_with(e: &VKError) -> ! {
+    eprintln!("error: {}", e);
+    eprintln!("{}", e.diagnosis());
+    std::process::exit(1);
 }
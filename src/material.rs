@@ -0,0 +1,223 @@
+use crate::shader::reflect::{Block, Interface, Reflection};
+
+/// check a material fragment shader against the contract set out by the built in
+/// `model.frag`. a material may leave out any input or descriptor it doesn't need,
+/// and the members at the end of a block, but everything it does declare must match
+/// the contract exactly. members are matched by name, then checked for type and offset.
+/// returns a description of the first mismatch.
+pub fn validate(
+    material: &Reflection,
+    contract: &Reflection,
+    vertex_outputs: &Interface,
+) -> Result<(), String> {
+    for input in material.inputs.entries.iter() {
+        let name = input.name.as_ref().map_or("", String::as_str);
+        match vertex_outputs
+            .entries
+            .iter()
+            .find(|output| output.location.start == input.location.start)
+        {
+            Some(output) if output.format == input.format && output.location == input.location => {}
+            Some(output) => {
+                return Err(format!(
+                    "input `{}` at location {} is {:?}, the vertex shader provides {:?}",
+                    name, input.location.start, input.format, output.format
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "input `{}` at location {} isn't provided by the vertex shader",
+                    name, input.location.start
+                ))
+            }
+        }
+    }
+
+    let outputs = |interface: &Interface| {
+        interface
+            .entries
+            .iter()
+            .map(|entry| (entry.location.clone(), entry.format))
+            .collect::<Vec<_>>()
+    };
+    if outputs(&material.outputs) != outputs(&contract.outputs) {
+        return Err("materials must write a single vec4 colour to location 0".to_owned());
+    }
+
+    for (set, bindings) in material.layout.sets.iter().enumerate() {
+        for (binding, desc) in bindings.iter().enumerate() {
+            let desc = match desc {
+                Some(desc) => desc,
+                None => continue,
+            };
+            let expected = contract
+                .layout
+                .sets
+                .get(set)
+                .and_then(|bindings| bindings.get(binding))
+                .and_then(Option::as_ref)
+                .ok_or_else(|| {
+                    format!("set {} binding {} isn't part of the contract", set, binding)
+                })?;
+            if let Err(e) = expected.is_superset_of(desc) {
+                return Err(format!(
+                    "set {} binding {} doesn't match the contract: {:?}",
+                    set, binding, e
+                ));
+            }
+        }
+    }
+
+    for block in material.blocks.iter() {
+        let expected = contract
+            .blocks
+            .iter()
+            .find(|b| b.set == block.set && b.binding == block.binding)
+            .ok_or_else(|| format!("block {} isn't part of the contract", block.name))?;
+        check_block(block, expected)?;
+    }
+
+    Ok(())
+}
+
+fn check_block(block: &Block, expected: &Block) -> Result<(), String> {
+    for member in block.members.iter() {
+        let wanted = match expected.members.iter().find(|m| m.name == member.name) {
+            Some(wanted) => wanted,
+            None => {
+                let names: Vec<&str> = expected.members.iter().map(|m| m.name.as_str()).collect();
+                return Err(format!(
+                    "{} has no member `{}`, it has {}",
+                    expected.name,
+                    member.name,
+                    names.join(", ")
+                ));
+            }
+        };
+        if member.ty != wanted.ty {
+            return Err(format!(
+                "{}.{} is a {}, the contract has a {}",
+                expected.name, member.name, member.ty, wanted.ty
+            ));
+        }
+        // the usual cause is a member left out before it, which shifts everything after.
+        if member.offset != wanted.offset {
+            return Err(format!(
+                "{}.{} is at offset {}, the contract has it at {}, declare the members before it too",
+                expected.name, member.name, member.offset, wanted.offset
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::{RuntimeShader, ShaderSource, ShaderStage};
+
+    fn reflect(stage: ShaderStage, name: &'static str, glsl: &'static str) -> Reflection {
+        RuntimeShader::reflect(&ShaderSource::Embedded(name, glsl), stage).unwrap()
+    }
+
+    /// check a material against the built in contract and vertex shader.
+    fn check(glsl: &'static str) -> Result<(), String> {
+        let contract = reflect(
+            ShaderStage::Fragment,
+            "model.frag",
+            include_str!("../shaders/model.frag"),
+        );
+        let vertex = reflect(
+            ShaderStage::Vertex,
+            "model.vert",
+            include_str!("../shaders/model.vert"),
+        );
+        let material = reflect(ShaderStage::Fragment, "material.frag", glsl);
+        validate(&material, &contract, &vertex.outputs)
+    }
+
+    #[test]
+    fn bundled_shaders_fit() {
+        assert_eq!(check(include_str!("../shaders/model.frag")), Ok(()));
+        assert_eq!(
+            check(include_str!("../shaders/materials/fresnel.frag")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn skipped_member() {
+        let result = check(
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(set = 0, binding = 0) uniform Frame {
+                mat4 view;
+                mat4 proj;
+                vec4 sh[9];
+            } frame;
+            void main() {
+                f_color = frame.sh[0];
+            }",
+        );
+        assert_eq!(
+            result,
+            Err("Frame.sh is at offset 128, the contract has it at 192, \
+                 declare the members before it too"
+                .to_owned())
+        );
+    }
+
+    #[test]
+    fn unknown_member() {
+        let result = check(
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(set = 0, binding = 1) uniform Material {
+                vec4 params;
+                float roughness;
+            } material;
+            void main() {
+                f_color = material.params * material.roughness;
+            }",
+        );
+        assert_eq!(
+            result,
+            Err("Material has no member `roughness`, it has params, diffuse".to_owned())
+        );
+    }
+
+    #[test]
+    fn flat_environment() {
+        let result = check(
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(set = 1, binding = 0) uniform sampler2D environment;
+            void main() {
+                f_color = texture(environment, vec2(0.5));
+            }",
+        );
+        let error = result.unwrap_err();
+        assert!(
+            error.starts_with("set 1 binding 0 doesn't match the contract"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn extra_outputs() {
+        let result = check(
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(location = 1) out vec4 f_normal;
+            void main() {
+                f_color = vec4(1.0);
+                f_normal = vec4(0.0, 0.0, 1.0, 0.0);
+            }",
+        );
+        assert_eq!(
+            result,
+            Err("materials must write a single vec4 colour to location 0".to_owned())
+        );
+    }
+}
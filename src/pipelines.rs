@@ -0,0 +1,534 @@
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::geometry;
+use crate::material;
+use crate::shader::reflect::Reflection;
+use crate::shader::{RuntimeShader, ShaderError, ShaderSource, ShaderStage};
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::descriptor::pipeline_layout::PipelineLayoutDesc;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
+use vulkano::image::attachment::AttachmentImage;
+use vulkano::image::{ImmutableImage, SwapchainImage};
+use vulkano::pipeline::depth_stencil::{Compare, DepthStencil};
+use vulkano::pipeline::vertex::{SingleBufferDefinition, TwoBuffersDefinition};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::{
+    GraphicsPipeline, GraphicsPipelineAbstract, GraphicsPipelineCreationError,
+};
+use vulkano::sampler::Sampler;
+
+use winit::Window;
+
+/// the depth buffer format shared by every pipeline.
+pub static DEPTH_FORMAT: Format = Format::D16Unorm;
+
+// the uniform blocks as rust types. the pipelines compile their spir-v at runtime
+// so it can be reloaded, the macros still check the shipped shaders at build time.
+#[allow(dead_code)]
+pub mod model_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/model.frag",
+    }
+}
+
+#[allow(dead_code)]
+pub mod sky_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/sky.vert",
+    }
+}
+
+/// the four stages every pipeline is built from.
+pub struct ShaderSet {
+    pub model_vs: RuntimeShader,
+    /// the built in `model.frag` or a user supplied material.
+    pub model_fs: RuntimeShader,
+    pub sky_vs: RuntimeShader,
+    pub sky_fs: RuntimeShader,
+    /// the interface of the built in `model.frag`, which materials are checked against.
+    contract: Reflection,
+}
+
+static SHADERS: [(&str, &str, ShaderStage); 4] = [
+    (
+        "model.vert",
+        include_str!("../shaders/model.vert"),
+        ShaderStage::Vertex,
+    ),
+    (
+        "model.frag",
+        include_str!("../shaders/model.frag"),
+        ShaderStage::Fragment,
+    ),
+    (
+        "sky.vert",
+        include_str!("../shaders/sky.vert"),
+        ShaderStage::Vertex,
+    ),
+    (
+        "sky.frag",
+        include_str!("../shaders/sky.frag"),
+        ShaderStage::Fragment,
+    ),
+];
+
+impl ShaderSet {
+    /// compile the shaders built in to the binary, or the files of the same
+    /// names in `dir` so they can be edited while running. `material` replaces
+    /// the model's fragment shader, and has to fit the material contract.
+    pub fn load(
+        device: &Arc<Device>,
+        dir: Option<&Path>,
+        material: Option<&Path>,
+    ) -> Result<Self, ShaderError> {
+        let mut stages = SHADERS.iter().map(|&(name, source, stage)| {
+            let source = match (dir, material) {
+                (_, Some(material)) if name == "model.frag" => {
+                    ShaderSource::File(material.to_path_buf())
+                }
+                (Some(dir), _) => ShaderSource::File(dir.join(name)),
+                _ => ShaderSource::Embedded(name, source),
+            };
+            RuntimeShader::compile(device.clone(), source, stage)
+        });
+
+        let model_vs = stages.next().unwrap()?;
+        let model_fs = stages.next().unwrap()?;
+        let sky_vs = stages.next().unwrap()?;
+        let sky_fs = stages.next().unwrap()?;
+
+        // the contract is always the built in model.frag, which has usually just been
+        // compiled as the model's own fragment shader. only reflect it again when not.
+        let contract = match model_fs.source {
+            ShaderSource::Embedded(..) => model_fs.reflection.clone(),
+            ShaderSource::File(_) => {
+                let (name, source, stage) = SHADERS[1];
+                RuntimeShader::reflect(&ShaderSource::Embedded(name, source), stage)?
+            }
+        };
+
+        let shaders = ShaderSet {
+            model_vs,
+            model_fs,
+            sky_vs,
+            sky_fs,
+            contract,
+        };
+        shaders.validate()?;
+        Ok(shaders)
+    }
+
+    /// check the model's fragment shader against the material contract.
+    pub fn validate(&self) -> Result<(), ShaderError> {
+        material::validate(
+            &self.model_fs.reflection,
+            &self.contract,
+            &self.model_vs.reflection.outputs,
+        )
+        .map_err(ShaderError::Contract)
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut RuntimeShader> {
+        vec![
+            &mut self.model_vs,
+            &mut self.model_fs,
+            &mut self.sky_vs,
+            &mut self.sky_fs,
+        ]
+        .into_iter()
+    }
+
+    /// the source files of every stage loaded from disk.
+    pub fn paths(&self) -> Vec<&Path> {
+        [&self.model_vs, &self.model_fs, &self.sky_vs, &self.sky_fs]
+            .iter()
+            .filter_map(|shader| shader.source.path())
+            .collect()
+    }
+}
+
+/// every pipeline the renderer draws with, plus the framebuffers they render in to.
+/// like the teapot example the viewport is baked in to each pipeline so the driver
+/// can optimize for it, which means everything here has to be rebuilt with the swapchain.
+/// https://computergraphics.stackexchange.com/questions/5742/vulkan-best-way-of-updating-pipeline-viewport
+pub struct PipelineRegistry {
+    device: Arc<Device>,
+    pub render_pass: Arc<RenderPassAbstract + Send + Sync>,
+    pub shaders: ShaderSet,
+    images: Vec<Arc<SwapchainImage<Window>>>,
+    /// shaded meshes, reflecting and lit by the environment.
+    pub model: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    /// the fullscreen environment, drawn after everything else.
+    pub skybox: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    pub framebuffers: Vec<Arc<FramebufferAbstract + Send + Sync>>,
+    /// draw the model's edges rather than filling it in, where the device can.
+    wireframe: bool,
+    /// goes up whenever a reload changes what descriptors a pipeline takes, so
+    /// descriptor sets built against the old layout know to be rebuilt.
+    pub layout_version: u32,
+}
+
+/// the pipelines and framebuffers for one set of swapchain images.
+type Built = (
+    Arc<GraphicsPipelineAbstract + Send + Sync>,
+    Arc<GraphicsPipelineAbstract + Send + Sync>,
+    Vec<Arc<FramebufferAbstract + Send + Sync>>,
+);
+
+impl PipelineRegistry {
+    /// compile the shaders and build every pipeline for the given swapchain images.
+    pub fn new(
+        device: Arc<Device>,
+        images: &[Arc<SwapchainImage<Window>>],
+        shaders: ShaderSet,
+    ) -> Result<Self, ShaderError> {
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: images[0].swapchain().format(),
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: DEPTH_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {depth}
+                }
+            )
+            .unwrap(),
+        ) as Arc<RenderPassAbstract + Send + Sync>;
+
+        let (model, skybox, framebuffers) =
+            Self::build(&device, &render_pass, &shaders, images, false)?;
+
+        Ok(PipelineRegistry {
+            device,
+            render_pass,
+            shaders,
+            images: images.to_vec(),
+            model,
+            skybox,
+            framebuffers,
+            wireframe: false,
+            layout_version: 0,
+        })
+    }
+
+    /// rebuild the pipelines and framebuffers after the swapchain was recreated.
+    /// on error everything is left as it was.
+    pub fn rebuild(&mut self, images: &[Arc<SwapchainImage<Window>>]) -> Result<(), ShaderError> {
+        let (model, skybox, framebuffers) = Self::build(
+            &self.device,
+            &self.render_pass,
+            &self.shaders,
+            images,
+            self.wireframe,
+        )?;
+        self.images = images.to_vec();
+        self.model = model;
+        self.skybox = skybox;
+        self.framebuffers = framebuffers;
+        Ok(())
+    }
+
+    /// switch the model between filled and wireframe, returning whether it's now wireframe.
+    /// lines need the fill_mode_non_solid feature, without it the model stays filled.
+    pub fn toggle_wireframe(&mut self) -> bool {
+        if !self.device.enabled_features().fill_mode_non_solid {
+            println!("this device can't draw wireframes");
+            return false;
+        }
+        self.wireframe = !self.wireframe;
+        let images = self.images.clone();
+        if let Err(e) = self.rebuild(&images) {
+            println!("{}", e);
+            self.wireframe = !self.wireframe;
+        }
+        self.wireframe
+    }
+
+    /// recompile any stage whose source is in `changed` and rebuild the pipelines.
+    /// on any error the message is printed and the last good pipelines are kept.
+    pub fn reload(&mut self, changed: &[PathBuf]) {
+        let device = self.device.clone();
+        let mut compiled = Vec::new();
+        for (i, shader) in self.shaders.iter_mut().enumerate() {
+            if !changed.iter().any(|path| shader.is_source_of(path)) {
+                continue;
+            }
+            match shader.recompile(device.clone()) {
+                Ok(new_shader) => compiled.push((i, new_shader)),
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            }
+        }
+        if compiled.is_empty() {
+            return;
+        }
+
+        // swap the new stages in, and back out again if they don't link.
+        let mut previous = Vec::new();
+        for (i, new_shader) in compiled {
+            let shader = self.shaders.iter_mut().nth(i).unwrap();
+            previous.push((i, std::mem::replace(shader, new_shader)));
+        }
+
+        let built = self.shaders.validate().and_then(|_| {
+            Self::build(
+                &self.device,
+                &self.render_pass,
+                &self.shaders,
+                &self.images,
+                self.wireframe,
+            )
+        });
+        match built {
+            Ok((model, skybox, framebuffers)) => {
+                println!("reloaded shaders");
+                if !same_layout(&*self.model, &*model) || !same_layout(&*self.skybox, &*skybox) {
+                    self.layout_version += 1;
+                }
+                self.model = model;
+                self.skybox = skybox;
+                self.framebuffers = framebuffers;
+            }
+            Err(e) => {
+                println!("{}", e);
+                for (i, old_shader) in previous {
+                    *self.shaders.iter_mut().nth(i).unwrap() = old_shader;
+                }
+            }
+        }
+    }
+
+    /// set 1 of the model pipeline, the environment it reflects. it only changes with
+    /// the environment or the layout, so it's built once and kept.
+    pub fn environment_set(
+        &self,
+        environment: &Arc<ImmutableImage<Format>>,
+        sampler: &Arc<Sampler>,
+    ) -> Arc<DescriptorSet + Send + Sync> {
+        Arc::new(
+            PersistentDescriptorSet::start(self.model.clone(), 1)
+                .add_sampled_image(environment.clone(), sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn build(
+        device: &Arc<Device>,
+        render_pass: &Arc<RenderPassAbstract + Send + Sync>,
+        shaders: &ShaderSet,
+        images: &[Arc<SwapchainImage<Window>>],
+        wireframe: bool,
+    ) -> Result<Built, ShaderError> {
+        let dimensions = images[0].dimensions();
+
+        let depth_buffer =
+            AttachmentImage::transient(device.clone(), dimensions, DEPTH_FORMAT).unwrap();
+
+        let framebuffers = images
+            .iter()
+            .map(|image| {
+                Arc::new(
+                    Framebuffer::start(render_pass.clone())
+                        .add(image.clone())
+                        .unwrap()
+                        .add(depth_buffer.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                ) as Arc<FramebufferAbstract + Send + Sync>
+            })
+            .collect::<Vec<_>>();
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        let model = GraphicsPipeline::start()
+            .vertex_input(TwoBuffersDefinition::<geometry::Vertex, geometry::Normal>::new())
+            .vertex_shader(shaders.model_vs.entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .viewports(iter::once(viewport.clone()))
+            .fragment_shader(
+                shaders
+                    .model_fs
+                    .entry_point_with_layout(shaders.contract.layout.clone()),
+                (),
+            )
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap());
+        let model = if wireframe {
+            model.polygon_mode_line()
+        } else {
+            model
+        };
+        let model = Arc::new(model.build(device.clone()).map_err(pipeline_error)?);
+
+        // the sky sits exactly on the far plane, so it needs less or equal to pass
+        // against the cleared depth, and never writes since nothing is drawn after it.
+        let skybox = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(SingleBufferDefinition::<geometry::Vertex>::new())
+                .vertex_shader(shaders.sky_vs.entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .viewports(iter::once(viewport))
+                .fragment_shader(shaders.sky_fs.entry_point(), ())
+                .depth_stencil(DepthStencil {
+                    depth_write: false,
+                    depth_compare: Compare::LessOrEqual,
+                    ..DepthStencil::simple_depth_test()
+                })
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .map_err(pipeline_error)?,
+        );
+
+        Ok((model, skybox, framebuffers))
+    }
+}
+
+fn pipeline_error(e: GraphicsPipelineCreationError) -> ShaderError {
+    ShaderError::Pipeline(format!("{:?}", e))
+}
+
+/// whether descriptor sets built for one pipeline can be bound to the other.
+fn same_layout(a: &GraphicsPipelineAbstract, b: &GraphicsPipelineAbstract) -> bool {
+    if a.num_sets() != b.num_sets() {
+        return false;
+    }
+    (0..a.num_sets()).all(|set| {
+        let bindings = a.num_bindings_in_set(set);
+        bindings == b.num_bindings_in_set(set)
+            && (0..bindings.unwrap_or(0)).all(|binding| {
+                match (a.descriptor(set, binding), b.descriptor(set, binding)) {
+                    (Some(a), Some(b)) => {
+                        a.is_superset_of(&b).is_ok() && b.is_superset_of(&a).is_ok()
+                    }
+                    (None, None) => true,
+                    _ => false,
+                }
+            })
+    })
+}
+
+/// the uniform buffers one frame in flight writes in to, and the descriptor sets
+/// pointing at them. the sets are kept until a pipeline layout or the environment changes.
+pub struct FrameUniforms {
+    frame: Arc<CpuAccessibleBuffer<model_fs::ty::Frame>>,
+    material: Arc<CpuAccessibleBuffer<model_fs::ty::Material>>,
+    sky: Arc<CpuAccessibleBuffer<sky_vs::ty::Data>>,
+    sets: Option<FrameSets>,
+}
+
+/// set 0 of the model pipeline and the skybox's only set.
+pub struct FrameSets {
+    pub model: Arc<DescriptorSet + Send + Sync>,
+    pub skybox: Arc<DescriptorSet + Send + Sync>,
+    /// the registry's `layout_version` these were built for.
+    layout_version: u32,
+}
+
+impl FrameUniforms {
+    pub fn new(device: &Arc<Device>) -> Self {
+        // every frame writes all of them before drawing, so there's nothing to fill in yet.
+        unsafe {
+            FrameUniforms {
+                frame: CpuAccessibleBuffer::uninitialized(
+                    device.clone(),
+                    BufferUsage::uniform_buffer(),
+                )
+                .unwrap(),
+                material: CpuAccessibleBuffer::uninitialized(
+                    device.clone(),
+                    BufferUsage::uniform_buffer(),
+                )
+                .unwrap(),
+                sky: CpuAccessibleBuffer::uninitialized(
+                    device.clone(),
+                    BufferUsage::uniform_buffer(),
+                )
+                .unwrap(),
+                sets: None,
+            }
+        }
+    }
+
+    /// fill in the uniforms, the gpu has to be done with the last frame that read them.
+    pub fn write(
+        &self,
+        frame: model_fs::ty::Frame,
+        material: model_fs::ty::Material,
+        sky: sky_vs::ty::Data,
+    ) {
+        *self.frame.write().unwrap() = frame;
+        *self.material.write().unwrap() = material;
+        *self.sky.write().unwrap() = sky;
+    }
+
+    /// forget the descriptor sets, for when the environment changed.
+    pub fn invalidate(&mut self) {
+        self.sets = None;
+    }
+
+    /// the descriptor sets for drawing with these uniforms, built the first time round
+    /// and again whenever the pipeline layouts change.
+    pub fn sets(
+        &mut self,
+        pipelines: &PipelineRegistry,
+        environment: &Arc<ImmutableImage<Format>>,
+        sampler: &Arc<Sampler>,
+    ) -> &FrameSets {
+        let stale = self
+            .sets
+            .as_ref()
+            .map_or(true, |sets| sets.layout_version != pipelines.layout_version);
+        if stale {
+            let model = PersistentDescriptorSet::start(pipelines.model.clone(), 0)
+                .add_buffer(self.frame.clone())
+                .unwrap()
+                .add_buffer(self.material.clone())
+                .unwrap()
+                .build()
+                .unwrap();
+            let skybox = PersistentDescriptorSet::start(pipelines.skybox.clone(), 0)
+                .add_buffer(self.sky.clone())
+                .unwrap()
+                .add_sampled_image(environment.clone(), sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap();
+            self.sets = Some(FrameSets {
+                model: Arc::new(model),
+                skybox: Arc::new(skybox),
+                layout_version: pipelines.layout_version,
+            });
+        }
+        self.sets.as_ref().unwrap()
+    }
+}
@@ -0,0 +1,247 @@
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::{GraphicsEntryPoint, GraphicsShaderType, ShaderModule};
+
+/// spir-v reflection, just enough to build pipelines from runtime compiled shaders
+pub mod reflect;
+
+use reflect::{Interface, Layout, Reflection};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+/// where the glsl for a stage comes from.
+#[derive(Clone, Debug)]
+pub enum ShaderSource {
+    /// built in to the binary, named for error messages.
+    Embedded(&'static str, &'static str),
+    /// read from disk every time it is compiled.
+    File(PathBuf),
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// the source file could not be read.
+    Io(PathBuf, std::io::Error),
+    /// glslang rejected the source, holds the compiler's messages.
+    Compile(String),
+    /// the spir-v uses something we can't describe to vulkan.
+    Reflect(String),
+    /// a material shader doesn't fit the interface the model pipeline provides.
+    Contract(String),
+    /// vulkan wouldn't make a module or pipeline out of the compiled shaders.
+    Pipeline(String),
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShaderError::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            ShaderError::Compile(messages) => write!(f, "{}", messages),
+            ShaderError::Reflect(e) => write!(f, "{}", e),
+            ShaderError::Contract(e) => write!(f, "the material doesn't fit the contract, {}", e),
+            ShaderError::Pipeline(e) => {
+                write!(f, "could not build a pipeline from the shaders, {}", e)
+            }
+        }
+    }
+}
+
+/// a shader stage compiled from glsl at runtime, along with its reflected interface
+/// so a pipeline can be built without knowing the shader at compile time.
+pub struct RuntimeShader {
+    pub source: ShaderSource,
+    pub stage: ShaderStage,
+    pub reflection: Reflection,
+    module: Arc<ShaderModule>,
+}
+
+impl ShaderSource {
+    /// the file to watch for changes, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ShaderSource::Embedded(..) => None,
+            ShaderSource::File(path) => Some(path),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            ShaderSource::Embedded(name, _) => (*name).to_owned(),
+            ShaderSource::File(path) => path.display().to_string(),
+        }
+    }
+
+    fn read(&self) -> Result<String, ShaderError> {
+        match self {
+            ShaderSource::Embedded(_, source) => Ok((*source).to_owned()),
+            ShaderSource::File(path) => {
+                std::fs::read_to_string(path).map_err(|e| ShaderError::Io(path.clone(), e))
+            }
+        }
+    }
+}
+
+impl RuntimeShader {
+    /// compile and reflect a stage.
+    pub fn compile(
+        device: Arc<Device>,
+        source: ShaderSource,
+        stage: ShaderStage,
+    ) -> Result<Self, ShaderError> {
+        let (artifact, reflection) = Self::compile_spirv(&source, stage)?;
+
+        // safe as long as the spir-v is valid, which shaderc just vouched for.
+        let module = unsafe { ShaderModule::new(device, artifact.as_binary_u8()) }
+            .map_err(|e| ShaderError::Pipeline(format!("{}: {:?}", source.name(), e)))?;
+
+        Ok(RuntimeShader {
+            source,
+            stage,
+            reflection,
+            module,
+        })
+    }
+
+    /// compile a stage just to reflect its interface, without making a module of it.
+    pub fn reflect(source: &ShaderSource, stage: ShaderStage) -> Result<Reflection, ShaderError> {
+        Self::compile_spirv(source, stage).map(|(_, reflection)| reflection)
+    }
+
+    fn compile_spirv(
+        source: &ShaderSource,
+        stage: ShaderStage,
+    ) -> Result<(shaderc::CompilationArtifact, Reflection), ShaderError> {
+        let glsl = source.read()?;
+
+        let mut compiler = shaderc::Compiler::new().ok_or_else(|| {
+            ShaderError::Compile("could not start the shader compiler".to_owned())
+        })?;
+        let kind = match stage {
+            ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+            ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+        };
+        let artifact = compiler
+            .compile_into_spirv(&glsl, kind, &source.name(), "main", None)
+            .map_err(|e| ShaderError::Compile(e.to_string()))?;
+
+        let stages = match stage {
+            ShaderStage::Vertex => ShaderStages {
+                vertex: true,
+                ..ShaderStages::none()
+            },
+            ShaderStage::Fragment => ShaderStages {
+                fragment: true,
+                ..ShaderStages::none()
+            },
+        };
+        let reflection = reflect::reflect(artifact.as_binary(), stages)
+            .map_err(|e| ShaderError::Reflect(format!("{}: {}", source.name(), e)))?;
+
+        Ok((artifact, reflection))
+    }
+
+    /// compile the same source again, leaving this shader untouched on failure.
+    pub fn recompile(&self, device: Arc<Device>) -> Result<Self, ShaderError> {
+        Self::compile(device, self.source.clone(), self.stage)
+    }
+
+    /// true if `changed`, as reported by a `ShaderWatcher`, is this shader's source file.
+    pub fn is_source_of(&self, changed: &Path) -> bool {
+        match self.source.path().and_then(|path| path.canonicalize().ok()) {
+            Some(path) => changed.canonicalize().ok().map_or(false, |c| c == path),
+            None => false,
+        }
+    }
+
+    pub fn entry_point(&self) -> GraphicsEntryPoint<(), Interface, Interface, Layout> {
+        self.entry_point_with_layout(self.reflection.layout.clone())
+    }
+
+    /// an entry point claiming the descriptors in `layout` rather than the ones it declares,
+    /// so the pipeline layout stays the same whichever subset of it the shader uses.
+    /// `layout` must be a superset of the shader's own.
+    pub fn entry_point_with_layout(
+        &self,
+        layout: Layout,
+    ) -> GraphicsEntryPoint<(), Interface, Interface, Layout> {
+        let ty = match self.stage {
+            ShaderStage::Vertex => GraphicsShaderType::Vertex,
+            ShaderStage::Fragment => GraphicsShaderType::Fragment,
+        };
+        let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        // the interfaces and layout come straight from the module's own spir-v.
+        unsafe {
+            self.module.graphics_entry_point(
+                main,
+                self.reflection.inputs.clone(),
+                self.reflection.outputs.clone(),
+                layout,
+                ty,
+            )
+        }
+    }
+}
+
+/// watches shader source files and reports the ones that changed.
+pub struct ShaderWatcher {
+    // never read, but the watch stops when it is dropped.
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+}
+
+impl ShaderWatcher {
+    /// watch the directories holding `paths`, editors often replace a file rather than
+    /// writing to it, which a watch on the file itself would lose track of.
+    pub fn new<'a, I>(paths: I) -> notify::Result<Self>
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        let (tx, events) = channel();
+        let mut watcher = notify::watcher(tx, Duration::from_millis(100))?;
+
+        let mut dirs: Vec<PathBuf> = paths
+            .into_iter()
+            .filter_map(|path| path.canonicalize().ok())
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(ShaderWatcher {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// every file written since the last call, without blocking.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self
+            .events
+            .try_iter()
+            .filter_map(|event| match event {
+                DebouncedEvent::Write(path)
+                | DebouncedEvent::Create(path)
+                | DebouncedEvent::Rename(_, path) => Some(path),
+                _ => None,
+            })
+            .collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
@@ -0,0 +1,773 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use vulkano::descriptor::descriptor::{
+    DescriptorBufferDesc, DescriptorDesc, DescriptorDescTy, DescriptorImageDesc,
+    DescriptorImageDescArray, DescriptorImageDescDimensions, ShaderStages,
+};
+use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
+use vulkano::format::Format;
+use vulkano::pipeline::shader::{ShaderInterfaceDef, ShaderInterfaceDefEntry};
+
+static MAGIC: u32 = 0x0723_0203;
+
+// the handful of opcodes, decorations and storage classes we care about,
+// numbered as in the spir-v specification.
+const OP_NAME: u16 = 5;
+const OP_MEMBER_NAME: u16 = 6;
+const OP_TYPE_BOOL: u16 = 20;
+const OP_TYPE_INT: u16 = 21;
+const OP_TYPE_FLOAT: u16 = 22;
+const OP_TYPE_VECTOR: u16 = 23;
+const OP_TYPE_MATRIX: u16 = 24;
+const OP_TYPE_IMAGE: u16 = 25;
+const OP_TYPE_SAMPLER: u16 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+const OP_TYPE_ARRAY: u16 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+const OP_TYPE_STRUCT: u16 = 30;
+const OP_TYPE_POINTER: u16 = 32;
+const OP_CONSTANT: u16 = 43;
+const OP_VARIABLE: u16 = 59;
+const OP_DECORATE: u16 = 71;
+const OP_MEMBER_DECORATE: u16 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+/// the types a reflected variable can have, just detailed enough
+/// to work out formats, locations and descriptor kinds.
+#[derive(Clone, Debug)]
+enum Type {
+    Bool,
+    Int {
+        signed: bool,
+    },
+    Float {
+        width: u32,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Matrix {
+        column: u32,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+        arrayed: bool,
+        multisampled: bool,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage {
+        image: u32,
+    },
+    Array {
+        element: u32,
+        length: u32,
+    },
+    RuntimeArray {
+        element: u32,
+    },
+    Struct {
+        members: Vec<u32>,
+    },
+    Pointer {
+        pointee: u32,
+    },
+}
+
+/// a vertex input or fragment output, one or more consecutive locations of the same format.
+#[derive(Clone, Debug)]
+pub struct InterfaceEntry {
+    pub name: Option<String>,
+    pub location: Range<u32>,
+    pub format: Format,
+}
+
+/// the inputs or outputs of a shader stage.
+#[derive(Clone, Debug, Default)]
+pub struct Interface {
+    pub entries: Vec<InterfaceEntry>,
+}
+
+unsafe impl ShaderInterfaceDef for Interface {
+    type Iter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.entries
+            .iter()
+            .map(|entry| ShaderInterfaceDefEntry {
+                location: entry.location.clone(),
+                format: entry.format,
+                name: entry.name.clone().map(Cow::Owned),
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// the descriptor sets a shader stage uses, indexed by set then binding.
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    pub sets: Vec<Vec<Option<DescriptorDesc>>>,
+}
+
+unsafe impl PipelineLayoutDesc for Layout {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(|bindings| bindings.len())
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets
+            .get(set)
+            .and_then(|bindings| bindings.get(binding))
+            .and_then(|desc| desc.clone())
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        0
+    }
+
+    fn push_constants_range(&self, _num: usize) -> Option<PipelineLayoutDescPcRange> {
+        None
+    }
+}
+
+/// a member of a uniform or storage block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub name: String,
+    /// byte offset from the start of the block.
+    pub offset: u32,
+    /// the member's type written out glsl style, `vec4[9]`, `mat4` and so on.
+    pub ty: String,
+}
+
+/// the layout of a uniform or storage block bound at `set` and `binding`.
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub set: u32,
+    pub binding: u32,
+    pub name: String,
+    pub members: Vec<Member>,
+}
+
+/// everything the pipeline needs to know about a compiled shader stage.
+#[derive(Clone, Debug, Default)]
+pub struct Reflection {
+    pub inputs: Interface,
+    pub outputs: Interface,
+    pub layout: Layout,
+    pub blocks: Vec<Block>,
+}
+
+/// the ids decorated on a variable or type.
+#[derive(Default)]
+struct Decorations {
+    location: Option<u32>,
+    set: Option<u32>,
+    binding: Option<u32>,
+    block: bool,
+    buffer_block: bool,
+}
+
+/// read the interface and descriptor layout out of a spir-v module,
+/// `stages` are the shader stages the descriptors are visible to.
+pub fn reflect(words: &[u32], stages: ShaderStages) -> Result<Reflection, String> {
+    if words.len() < 5 || words[0] != MAGIC {
+        return Err("not a spir-v module".to_owned());
+    }
+
+    let mut names = HashMap::new();
+    let mut member_names = HashMap::new();
+    let mut member_offsets = HashMap::new();
+    let mut decorations: HashMap<u32, Decorations> = HashMap::new();
+    let mut types = HashMap::new();
+    let mut constants = HashMap::new();
+    let mut variables = Vec::new();
+
+    let mut i = 5;
+    while i < words.len() {
+        let count = (words[i] >> 16) as usize;
+        let opcode = (words[i] & 0xffff) as u16;
+        if count == 0 || i + count > words.len() {
+            return Err("truncated instruction".to_owned());
+        }
+        let operands = &words[i + 1..i + count];
+
+        match opcode {
+            OP_NAME => {
+                names.insert(operands[0], string(&operands[1..]));
+            }
+            OP_MEMBER_NAME => {
+                member_names.insert((operands[0], operands[1]), string(&operands[2..]));
+            }
+            OP_MEMBER_DECORATE => {
+                if operands[2] == DECORATION_OFFSET {
+                    member_offsets.insert((operands[0], operands[1]), operands[3]);
+                }
+            }
+            OP_DECORATE => {
+                let entry = decorations.entry(operands[0]).or_default();
+                match operands[1] {
+                    DECORATION_BLOCK => entry.block = true,
+                    DECORATION_BUFFER_BLOCK => entry.buffer_block = true,
+                    DECORATION_LOCATION => entry.location = Some(operands[2]),
+                    DECORATION_BINDING => entry.binding = Some(operands[2]),
+                    DECORATION_DESCRIPTOR_SET => entry.set = Some(operands[2]),
+                    _ => {}
+                }
+            }
+            OP_TYPE_BOOL => {
+                types.insert(operands[0], Type::Bool);
+            }
+            OP_TYPE_INT => {
+                types.insert(
+                    operands[0],
+                    Type::Int {
+                        signed: operands[2] != 0,
+                    },
+                );
+            }
+            OP_TYPE_FLOAT => {
+                types.insert(operands[0], Type::Float { width: operands[1] });
+            }
+            OP_TYPE_VECTOR => {
+                types.insert(
+                    operands[0],
+                    Type::Vector {
+                        component: operands[1],
+                        count: operands[2],
+                    },
+                );
+            }
+            OP_TYPE_MATRIX => {
+                types.insert(
+                    operands[0],
+                    Type::Matrix {
+                        column: operands[1],
+                        count: operands[2],
+                    },
+                );
+            }
+            OP_TYPE_IMAGE => {
+                types.insert(
+                    operands[0],
+                    Type::Image {
+                        dim: operands[2],
+                        arrayed: operands[4] != 0,
+                        multisampled: operands[5] != 0,
+                        sampled: operands[6],
+                    },
+                );
+            }
+            OP_TYPE_SAMPLER => {
+                types.insert(operands[0], Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                types.insert(operands[0], Type::SampledImage { image: operands[1] });
+            }
+            OP_TYPE_ARRAY => {
+                types.insert(
+                    operands[0],
+                    Type::Array {
+                        element: operands[1],
+                        length: operands[2],
+                    },
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                types.insert(
+                    operands[0],
+                    Type::RuntimeArray {
+                        element: operands[1],
+                    },
+                );
+            }
+            OP_TYPE_STRUCT => {
+                types.insert(
+                    operands[0],
+                    Type::Struct {
+                        members: operands[1..].to_vec(),
+                    },
+                );
+            }
+            OP_TYPE_POINTER => {
+                types.insert(
+                    operands[0],
+                    Type::Pointer {
+                        pointee: operands[2],
+                    },
+                );
+            }
+            OP_CONSTANT => {
+                constants.insert(operands[1], operands[2]);
+            }
+            OP_VARIABLE => {
+                variables.push((operands[0], operands[1], operands[2]));
+            }
+            _ => {}
+        }
+
+        i += count;
+    }
+
+    let module = Module {
+        names,
+        types,
+        constants,
+        decorations,
+    };
+    let mut reflection = Reflection::default();
+
+    for (pointer, id, storage) in variables {
+        let ty = match module.types.get(&pointer) {
+            Some(Type::Pointer { pointee }) => *pointee,
+            _ => return Err(format!("variable {} isn't a pointer", id)),
+        };
+        let decoration = module.decorations.get(&id);
+
+        match storage {
+            STORAGE_INPUT | STORAGE_OUTPUT => {
+                // built ins like gl_Position and gl_PerVertex have no location.
+                let location = match decoration.and_then(|d| d.location) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                let (format, locations) = module.interface_format(ty)?;
+                let entry = InterfaceEntry {
+                    name: module.names.get(&id).cloned(),
+                    location: location..location + locations,
+                    format,
+                };
+                if storage == STORAGE_INPUT {
+                    reflection.inputs.entries.push(entry);
+                } else {
+                    reflection.outputs.entries.push(entry);
+                }
+            }
+            STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                let name = module
+                    .names
+                    .get(&id)
+                    .cloned()
+                    .unwrap_or_else(|| id.to_string());
+                let set = decoration.and_then(|d| d.set).unwrap_or(0) as usize;
+                let binding = decoration
+                    .and_then(|d| d.binding)
+                    .ok_or_else(|| format!("{} has no binding", name))?
+                    as usize;
+
+                let (ty, array_count) = module.strip_array(ty);
+                let desc = DescriptorDesc {
+                    ty: module.descriptor_type(ty, storage, &name)?,
+                    array_count,
+                    stages: stages.clone(),
+                    readonly: storage != STORAGE_STORAGE_BUFFER,
+                };
+
+                if let Some(Type::Struct { members }) = module.types.get(&ty) {
+                    reflection.blocks.push(Block {
+                        set: set as u32,
+                        binding: binding as u32,
+                        name: module.names.get(&ty).cloned().unwrap_or_default(),
+                        members: members
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &member)| {
+                                let key = (ty, i as u32);
+                                Member {
+                                    name: member_names.get(&key).cloned().unwrap_or_default(),
+                                    offset: member_offsets.get(&key).cloned().unwrap_or(0),
+                                    ty: module.describe(member),
+                                }
+                            })
+                            .collect(),
+                    });
+                }
+
+                let sets = &mut reflection.layout.sets;
+                if sets.len() <= set {
+                    sets.resize(set + 1, Vec::new());
+                }
+                if sets[set].len() <= binding {
+                    sets[set].resize(binding + 1, None);
+                }
+                sets[set][binding] = Some(desc);
+            }
+            STORAGE_PUSH_CONSTANT => {
+                return Err("push constants aren't supported by runtime shaders".to_owned());
+            }
+            _ => {}
+        }
+    }
+
+    // keep the interfaces in location order so mismatches read sensibly.
+    reflection
+        .inputs
+        .entries
+        .sort_by_key(|entry| entry.location.start);
+    reflection
+        .outputs
+        .entries
+        .sort_by_key(|entry| entry.location.start);
+
+    Ok(reflection)
+}
+
+struct Module {
+    names: HashMap<u32, String>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+}
+
+impl Module {
+    fn get(&self, id: u32) -> Result<&Type, String> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| format!("unknown type id {}", id))
+    }
+
+    /// the element type of a (possibly) arrayed descriptor and how many there are.
+    fn strip_array(&self, ty: u32) -> (u32, u32) {
+        match self.types.get(&ty) {
+            Some(Type::Array { element, length }) => {
+                (*element, self.constants.get(length).cloned().unwrap_or(1))
+            }
+            Some(Type::RuntimeArray { element }) => (*element, 1),
+            _ => (ty, 1),
+        }
+    }
+
+    fn descriptor_type(
+        &self,
+        ty: u32,
+        storage: u32,
+        name: &str,
+    ) -> Result<DescriptorDescTy, String> {
+        let decoration = self.decorations.get(&ty);
+        match (self.get(ty)?, storage) {
+            (Type::Struct { .. }, STORAGE_UNIFORM)
+            | (Type::Struct { .. }, STORAGE_STORAGE_BUFFER) => {
+                let storage = storage == STORAGE_STORAGE_BUFFER
+                    || decoration.map_or(false, |d| d.buffer_block && !d.block);
+                Ok(DescriptorDescTy::Buffer(DescriptorBufferDesc {
+                    dynamic: None,
+                    storage,
+                }))
+            }
+            (Type::SampledImage { image }, _) => Ok(DescriptorDescTy::CombinedImageSampler(
+                self.image_desc(*image)?,
+            )),
+            (Type::Image { sampled, .. }, _) => {
+                let desc = self.image_desc(ty)?;
+                if *sampled == 2 {
+                    Ok(DescriptorDescTy::Image(DescriptorImageDesc {
+                        sampled: false,
+                        ..desc
+                    }))
+                } else {
+                    Ok(DescriptorDescTy::Image(desc))
+                }
+            }
+            (Type::Sampler, _) => Ok(DescriptorDescTy::Sampler),
+            _ => Err(format!("{} isn't a descriptor type we understand", name)),
+        }
+    }
+
+    fn image_desc(&self, image: u32) -> Result<DescriptorImageDesc, String> {
+        match self.get(image)? {
+            Type::Image {
+                dim,
+                arrayed,
+                multisampled,
+                ..
+            } => Ok(DescriptorImageDesc {
+                sampled: true,
+                dimensions: match dim {
+                    0 => DescriptorImageDescDimensions::OneDimensional,
+                    1 => DescriptorImageDescDimensions::TwoDimensional,
+                    2 => DescriptorImageDescDimensions::ThreeDimensional,
+                    3 => DescriptorImageDescDimensions::Cube,
+                    _ => return Err(format!("unsupported image dimensionality {}", dim)),
+                },
+                format: None,
+                multisampled: *multisampled,
+                array_layers: if *arrayed {
+                    DescriptorImageDescArray::Arrayed { max_layers: None }
+                } else {
+                    DescriptorImageDescArray::NonArrayed
+                },
+            }),
+            _ => Err(format!("type {} isn't an image", image)),
+        }
+    }
+
+    /// a type written out the way it would be declared in glsl.
+    fn describe(&self, ty: u32) -> String {
+        let prefix = |component: u32| match self.types.get(&component) {
+            Some(Type::Int { signed: true }) => "i",
+            Some(Type::Int { signed: false }) => "u",
+            Some(Type::Bool) => "b",
+            _ => "",
+        };
+        match self.types.get(&ty) {
+            Some(Type::Bool) => "bool".to_owned(),
+            Some(Type::Int { signed: true }) => "int".to_owned(),
+            Some(Type::Int { signed: false }) => "uint".to_owned(),
+            Some(Type::Float { .. }) => "float".to_owned(),
+            Some(Type::Vector { component, count }) => {
+                format!("{}vec{}", prefix(*component), count)
+            }
+            Some(Type::Matrix { column, count }) => match self.types.get(column) {
+                Some(Type::Vector { count: rows, .. }) if rows == count => format!("mat{}", count),
+                Some(Type::Vector { count: rows, .. }) => format!("mat{}x{}", count, rows),
+                _ => "matrix".to_owned(),
+            },
+            Some(Type::Array { element, length }) => format!(
+                "{}[{}]",
+                self.describe(*element),
+                self.constants.get(length).cloned().unwrap_or(0)
+            ),
+            Some(Type::RuntimeArray { element }) => format!("{}[]", self.describe(*element)),
+            Some(Type::Struct { .. }) => self
+                .names
+                .get(&ty)
+                .cloned()
+                .unwrap_or_else(|| "struct".to_owned()),
+            _ => "opaque".to_owned(),
+        }
+    }
+
+    /// the format of a vertex input or fragment output, and how many locations it spans.
+    fn interface_format(&self, ty: u32) -> Result<(Format, u32), String> {
+        match self.get(ty)? {
+            Type::Matrix { column, count } => {
+                let (format, _) = self.interface_format(*column)?;
+                Ok((format, *count))
+            }
+            Type::Array { element, length } => {
+                let (format, locations) = self.interface_format(*element)?;
+                let length = self.constants.get(length).cloned().unwrap_or(1);
+                Ok((format, locations * length))
+            }
+            Type::Vector { component, count } => Ok((self.scalar_format(*component, *count)?, 1)),
+            _ => Ok((self.scalar_format(ty, 1)?, 1)),
+        }
+    }
+
+    fn scalar_format(&self, scalar: u32, count: u32) -> Result<Format, String> {
+        let format = match (self.get(scalar)?, count) {
+            (Type::Float { width: 32 }, 1) => Format::R32Sfloat,
+            (Type::Float { width: 32 }, 2) => Format::R32G32Sfloat,
+            (Type::Float { width: 32 }, 3) => Format::R32G32B32Sfloat,
+            (Type::Float { width: 32 }, 4) => Format::R32G32B32A32Sfloat,
+            (Type::Int { signed: true }, 1) => Format::R32Sint,
+            (Type::Int { signed: true }, 2) => Format::R32G32Sint,
+            (Type::Int { signed: true }, 3) => Format::R32G32B32Sint,
+            (Type::Int { signed: true }, 4) => Format::R32G32B32A32Sint,
+            (Type::Int { signed: false }, 1) | (Type::Bool, 1) => Format::R32Uint,
+            (Type::Int { signed: false }, 2) => Format::R32G32Uint,
+            (Type::Int { signed: false }, 3) => Format::R32G32B32Uint,
+            (Type::Int { signed: false }, 4) => Format::R32G32B32A32Uint,
+            (ty, count) => return Err(format!("unsupported interface type {:?} x{}", ty, count)),
+        };
+        Ok(format)
+    }
+}
+
+/// decode a nul terminated utf-8 literal packed in to words.
+fn string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(kind: shaderc::ShaderKind, glsl: &str) -> Result<Reflection, String> {
+        let mut compiler = shaderc::Compiler::new().unwrap();
+        let artifact = compiler
+            .compile_into_spirv(glsl, kind, "test.glsl", "main", None)
+            .unwrap();
+        reflect(
+            artifact.as_binary(),
+            ShaderStages {
+                fragment: true,
+                ..ShaderStages::none()
+            },
+        )
+    }
+
+    fn member(name: &str, offset: u32, ty: &str) -> Member {
+        Member {
+            name: name.to_owned(),
+            offset,
+            ty: ty.to_owned(),
+        }
+    }
+
+    #[test]
+    fn block_members() {
+        let reflection = compile(
+            shaderc::ShaderKind::Fragment,
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(set = 0, binding = 2) uniform Frame {
+                mat4 view;
+                vec4 sh[9];
+                uint flags;
+                vec3 position;
+            } frame;
+            void main() {
+                f_color = frame.view[0] + frame.sh[8] + vec4(frame.position, float(frame.flags));
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(reflection.blocks.len(), 1);
+        let block = &reflection.blocks[0];
+        assert_eq!((block.set, block.binding), (0, 2));
+        assert_eq!(block.name, "Frame");
+        // std140 pads the vec3 out to the next 16 bytes.
+        assert_eq!(
+            block.members,
+            vec![
+                member("view", 0, "mat4"),
+                member("sh", 64, "vec4[9]"),
+                member("flags", 208, "uint"),
+                member("position", 224, "vec3"),
+            ]
+        );
+
+        let bindings = &reflection.layout.sets[0];
+        assert_eq!(bindings.len(), 3);
+        assert!(bindings[0].is_none() && bindings[1].is_none());
+        match bindings[2].as_ref().map(|desc| &desc.ty) {
+            Some(DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: false, .. })) => {}
+            other => panic!("expected a uniform buffer, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sampler_cube() {
+        let reflection = compile(
+            shaderc::ShaderKind::Fragment,
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(set = 1, binding = 0) uniform samplerCube environment;
+            void main() {
+                f_color = texture(environment, vec3(0.0, 0.0, 1.0));
+            }",
+        )
+        .unwrap();
+
+        assert!(reflection.blocks.is_empty());
+        assert!(reflection.layout.sets[0].is_empty());
+        let desc = reflection.layout.sets[1][0].as_ref().unwrap();
+        assert_eq!(desc.array_count, 1);
+        match desc.ty {
+            DescriptorDescTy::CombinedImageSampler(DescriptorImageDesc {
+                dimensions: DescriptorImageDescDimensions::Cube,
+                array_layers: DescriptorImageDescArray::NonArrayed,
+                multisampled: false,
+                ..
+            }) => {}
+            ref other => panic!("expected a cube sampler, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn interface_locations() {
+        let reflection = compile(
+            shaderc::ShaderKind::Vertex,
+            "#version 450
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in mat4 instance;
+            layout(location = 5) in uint id;
+            layout(location = 1) out vec3 v_normal;
+            layout(location = 0) out vec2 v_uv;
+            void main() {
+                v_normal = position * float(id);
+                v_uv = position.xy;
+                gl_Position = instance * vec4(position, 1.0);
+            }",
+        )
+        .unwrap();
+
+        let entries = |interface: &Interface| {
+            interface
+                .entries
+                .iter()
+                .map(|entry| {
+                    (
+                        entry.name.clone().unwrap(),
+                        entry.location.clone(),
+                        entry.format,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        // a mat4 takes a location per column.
+        assert_eq!(
+            entries(&reflection.inputs),
+            vec![
+                ("position".to_owned(), 0..1, Format::R32G32B32Sfloat),
+                ("instance".to_owned(), 1..5, Format::R32G32B32A32Sfloat),
+                ("id".to_owned(), 5..6, Format::R32Uint),
+            ]
+        );
+        // sorted by location, and gl_Position isn't in there.
+        assert_eq!(
+            entries(&reflection.outputs),
+            vec![
+                ("v_uv".to_owned(), 0..1, Format::R32G32Sfloat),
+                ("v_normal".to_owned(), 1..2, Format::R32G32B32Sfloat),
+            ]
+        );
+    }
+
+    #[test]
+    fn push_constants() {
+        let result = compile(
+            shaderc::ShaderKind::Fragment,
+            "#version 450
+            layout(location = 0) out vec4 f_color;
+            layout(push_constant) uniform Push {
+                vec4 tint;
+            } push;
+            void main() {
+                f_color = push.tint;
+            }",
+        );
+        assert_eq!(
+            result.err(),
+            Some("push constants aren't supported by runtime shaders".to_owned())
+        );
+    }
+}
@@ -1,11 +1,181 @@
 
 use crate::geometry::Vertex;
+use cgmath::Vector3;
 use image::{ImageFormat, RgbaImage};
+use std::borrow::Cow;
+use std::cmp;
+use std::path::Path;
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::{
+    Dimensions, ImageCreationError, ImageLayout, ImageUsage, ImmutableImage, MipmapsCount,
+};
+use vulkano::sync::GpuFuture;
+
+/// software decoders for bc6h and bc7
+mod bc;
+
+/// dds and ktx2 parsing
+pub mod container;
+
+/// analytic daylight sky
+pub mod procedural;
+
+use container::{ContainerError, Cubemap};
+use procedural::ProceduralSky;
 
 pub struct SkyBox {
+    /// the top mip of every face as 8 bit srgb.
     pub textures: Vec<RgbaImage>,
-    pub vertices: [Vertex; 24],
+    /// fullscreen triangle the sky is drawn with.
+    pub vertices: [Vertex; 3],
     pub indices: Vec<u32>,
+    /// width and height of every face in texels.
+    pub face_size: u32,
+    /// the original data when loaded from a dds or ktx2, mips and compression intact.
+    pub prebaked: Option<Cubemap>,
+}
+
+#[derive(Debug)]
+pub enum SkyBoxError {
+    /// the file could not be read.
+    Io(std::io::Error),
+    /// the image could not be decoded.
+    Image(image::ImageError),
+    /// the image dimensions don't match any layout we know how to slice.
+    UnknownLayout(u32, u32),
+    /// a dds or ktx2 file we can't use.
+    Container(ContainerError),
+    /// the device couldn't make a cubemap of this size, format or mip count.
+    Upload(ImageCreationError),
+}
+
+/// direction through the texel at (u, v) in [-1, 1] of a cubemap face,
+/// faces are in vulkan's +X, -X, +Y, -Y, +Z, -Z layer order.
+pub fn face_direction(face: usize, u: f32, v: f32) -> Vector3<f32> {
+    match face {
+        0 => Vector3::new(1.0, -v, -u),
+        1 => Vector3::new(-1.0, -v, u),
+        2 => Vector3::new(u, 1.0, v),
+        3 => Vector3::new(u, -1.0, -v),
+        4 => Vector3::new(u, -v, 1.0),
+        _ => Vector3::new(-u, -v, -1.0),
+    }
+}
+
+/// a linear channel to 8 bit srgb, clamped to [0, 1] first.
+fn encode_srgb(c: f32) -> u8 {
+    let c = c.max(0.0).min(1.0);
+    let c = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+/// the ways six cubemap faces are commonly packed in to a single image.
+/// crosses are laid out with +Y above and -Y below +Z:
+///
+/// ```text
+/// horizontal cross     vertical cross
+///    +Y                   +Y
+/// -X +Z +X -Z          -X +Z +X
+///    -Y                   -Y
+///                         -Z (upside down)
+/// ```
+///
+/// strips hold the faces in vulkan's +X, -X, +Y, -Y, +Z, -Z layer order.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CubemapLayout {
+    HorizontalCross,
+    VerticalCross,
+    HorizontalStrip,
+    VerticalStrip,
+}
+
+impl CubemapLayout {
+    /// guess the layout from the aspect ratio, returning it along with the face size.
+    pub fn detect(width: u32, height: u32) -> Option<(Self, u32)> {
+        let layout = if width * 3 == height * 4 {
+            (CubemapLayout::HorizontalCross, width / 4)
+        } else if width * 4 == height * 3 {
+            (CubemapLayout::VerticalCross, width / 3)
+        } else if width == height * 6 {
+            (CubemapLayout::HorizontalStrip, height)
+        } else if width * 6 == height {
+            (CubemapLayout::VerticalStrip, width)
+        } else {
+            return None;
+        };
+
+        if layout.1 == 0 {
+            None
+        } else {
+            Some(layout)
+        }
+    }
+
+    /// the tile each face lives in, in units of faces,
+    /// and whether it is stored rotated by 180 degrees.
+    fn tiles(self) -> [(u32, u32, bool); 6] {
+        match self {
+            CubemapLayout::HorizontalCross => [
+                (2, 1, false),
+                (0, 1, false),
+                (1, 0, false),
+                (1, 2, false),
+                (1, 1, false),
+                (3, 1, false),
+            ],
+            CubemapLayout::VerticalCross => [
+                (2, 1, false),
+                (0, 1, false),
+                (1, 0, false),
+                (1, 2, false),
+                (1, 1, false),
+                (1, 3, true),
+            ],
+            CubemapLayout::HorizontalStrip => [
+                (0, 0, false),
+                (1, 0, false),
+                (2, 0, false),
+                (3, 0, false),
+                (4, 0, false),
+                (5, 0, false),
+            ],
+            CubemapLayout::VerticalStrip => [
+                (0, 0, false),
+                (0, 1, false),
+                (0, 2, false),
+                (0, 3, false),
+                (0, 4, false),
+                (0, 5, false),
+            ],
+        }
+    }
+
+    /// cut the image up in to the six faces in vulkan's +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn slice(self, image: &RgbaImage, face_size: u32) -> Vec<RgbaImage> {
+        self.tiles()
+            .iter()
+            .map(|&(tile_x, tile_y, flipped)| {
+                let (origin_x, origin_y) = (tile_x * face_size, tile_y * face_size);
+                RgbaImage::from_fn(face_size, face_size, |x, y| {
+                    let (x, y) = if flipped {
+                        (face_size - 1 - x, face_size - 1 - y)
+                    } else {
+                        (x, y)
+                    };
+                    *image.get_pixel(origin_x + x, origin_y + y)
+                })
+            })
+            .collect()
+    }
 }
 // order is strange, I had to brute force this solution
 // as it is not the same as any examples delivered by
@@ -20,6 +190,7 @@ static TEXBYTES: [&[u8]; 6] = [
 ];
 
 impl SkyBox {
+    /// the built in arrakis skybox.
     pub fn new() -> Self {
 
         let textures: Vec<RgbaImage> = TEXBYTES
@@ -31,96 +202,146 @@ impl SkyBox {
             })
             .collect();
 
+        Self::from_faces(textures)
+    }
+
+    /// render an analytic sky in to the six faces, for look-dev without any assets.
+    pub fn procedural(sky: &ProceduralSky) -> Self {
+        Self::from_faces(sky.faces())
+    }
+
+    /// load a skybox from a dds or ktx2 cubemap, or from an image
+    /// with the faces packed in to a single cross or strip.
+    pub fn from_file(path: &Path) -> Result<Self, SkyBoxError> {
+        let bytes = std::fs::read(path).map_err(SkyBoxError::Io)?;
+
+        if Cubemap::is_container(&bytes) {
+            let cubemap = Cubemap::from_bytes(&bytes).map_err(SkyBoxError::Container)?;
+            let mut skybox = Self::from_faces(cubemap.to_faces());
+            skybox.prebaked = Some(cubemap);
+            return Ok(skybox);
+        }
+
+        let image = image::load_from_memory(&bytes)
+            .map_err(SkyBoxError::Image)?
+            .to_rgba();
+        let (width, height) = image.dimensions();
+        let (layout, face_size) = CubemapLayout::detect(width, height)
+            .ok_or(SkyBoxError::UnknownLayout(width, height))?;
+
+        Ok(Self::from_faces(layout.slice(&image, face_size)))
+    }
+
+    /// build a skybox from six square faces of equal size in +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn from_faces(textures: Vec<RgbaImage>) -> Self {
+        assert_eq!(textures.len(), 6, "a cubemap needs exactly six faces");
+        let face_size = textures[0].width();
+        assert!(
+            textures
+                .iter()
+                .all(|tex| tex.dimensions() == (face_size, face_size)),
+            "cubemap faces must be square and all the same size"
+        );
+
+        // a single triangle that covers the whole screen once clipped, in clip space.
+        // the vertex shader un-projects its corners in to view directions.
         let vertices = [
-            // Front
-            Vertex {
-                position: (-2.0, -2.0, 2.0),
-            },
-            Vertex {
-                position: (2.0, -2.0, 2.0),
-            },
-            Vertex {
-                position: (2.0, 2.0, 2.0),
-            },
-            Vertex {
-                position: (-2.0, 2.0, 2.0),
-            },
-            // Right
-            Vertex {
-                position: (2.0, -2.0, 2.0),
-            },
             Vertex {
-                position: (2.0, -2.0, -2.0),
+                position: (-1.0, -1.0, 1.0),
             },
             Vertex {
-                position: (2.0, 2.0, -2.0),
+                position: (3.0, -1.0, 1.0),
             },
             Vertex {
-                position: (2.0, 2.0, 2.0),
-            },
-            // Back
-            Vertex {
-                position: (-2.0, -2.0, -2.0),
-            },
-            Vertex {
-                position: (-2.0, 2.0, -2.0),
-            },
-            Vertex {
-                position: (2.0, 2.0, -2.0),
-            },
-            Vertex {
-                position: (2.0, -2.0, -2.0),
-            },
-            // Left
-            Vertex {
-                position: (-2.0, -2.0, 2.0),
-            },
-            Vertex {
-                position: (-2.0, 2.0, 2.0),
-            },
-            Vertex {
-                position: (-2.0, 2.0, -2.0),
-            },
-            Vertex {
-                position: (-2.0, -2.0, -2.0),
-            },
-            // Bottom
-            Vertex {
-                position: (-2.0, -2.0, 2.0),
-            },
-            Vertex {
-                position: (-2.0, -2.0, -2.0),
-            },
-            Vertex {
-                position: (2.0, -2.0, -2.0),
-            },
-            Vertex {
-                position: (2.0, -2.0, 2.0),
-            },
-            // Top
-            Vertex {
-                position: (-2.0, 2.0, 2.0),
-            },
-            Vertex {
-                position: (2.0, 2.0, 2.0),
-            },
-            Vertex {
-                position: (2.0, 2.0, -2.0),
-            },
-            Vertex {
-                position: (-2.0, 2.0, -2.0),
+                position: (-1.0, 3.0, 1.0),
             },
         ];
 
-        let indices = vec![
-            0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4, 8, 9, 10, 10, 11, 8, 12, 13, 14, 14, 15, 12, 16,
-            17, 18, 18, 19, 16, 20, 21, 22, 22, 23, 20,
-        ];
+        let indices = vec![0, 1, 2];
 
         SkyBox {
             textures,
             vertices,
             indices,
+            face_size,
+            prebaked: None,
         }
     }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.prebaked
+            .as_ref()
+            .map_or(1, |cubemap| cubemap.levels.len() as u32)
+    }
+
+    /// upload every face and mip level to the gpu. formats the device can't
+    /// sample are converted on the cpu first.
+    pub fn upload(
+        &self,
+        queue: Arc<Queue>,
+    ) -> Result<(Arc<ImmutableImage<Format>>, Box<GpuFuture>), SkyBoxError> {
+        let device = queue.device().clone();
+
+        let cubemap = match self.prebaked {
+            Some(ref cubemap) => Cow::Borrowed(cubemap),
+            None => Cow::Owned(Cubemap::from_faces(&self.textures)),
+        };
+        let cubemap = if cubemap.format.is_supported(&device) {
+            cubemap
+        } else {
+            println!(
+                "{:?} isn't supported by this device, converting the skybox on the cpu",
+                cubemap.format
+            );
+            Cow::Owned(cubemap.fallback())
+        };
+
+        let (image, init) = ImmutableImage::uninitialized(
+            device.clone(),
+            Dimensions::Cubemap {
+                size: cubemap.face_size,
+            },
+            cubemap.format.vk_format(),
+            MipmapsCount::Specific(cubemap.levels.len() as u32),
+            ImageUsage {
+                transfer_destination: true,
+                sampled: true,
+                ..ImageUsage::none()
+            },
+            ImageLayout::ShaderReadOnlyOptimal,
+            Some(queue.family()),
+        )
+        .map_err(SkyBoxError::Upload)?;
+        let init = Arc::new(init);
+
+        // one copy per mip level, each covering all six layers.
+        let mut commands =
+            AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())
+                .unwrap();
+        for (level, data) in cubemap.levels.iter().enumerate() {
+            let size = cmp::max(cubemap.face_size >> level, 1);
+            let staging = CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage::transfer_source(),
+                data.iter().cloned(),
+            )
+            .unwrap();
+
+            commands = commands
+                .copy_buffer_to_image_dimensions(
+                    staging,
+                    init.clone(),
+                    [0, 0, 0],
+                    [size, size, 1],
+                    0,
+                    6,
+                    level as u32,
+                )
+                .unwrap();
+        }
+
+        let future = commands.build().unwrap().execute(queue.clone()).unwrap();
+
+        Ok((image, Box::new(future) as Box<GpuFuture>))
+    }
 }
\ No newline at end of file
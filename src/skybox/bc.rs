@@ -0,0 +1,619 @@
+/// software decoders for the block compressed formats prebaked skyboxes ship in,
+/// used when the device can't sample them directly. both formats pack a 4x4 texel
+/// block in to 16 bytes, read least significant bit first.
+struct BitReader<'a> {
+    block: &'a [u8; 16],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(block: &'a [u8; 16]) -> Self {
+        BitReader { block, position: 0 }
+    }
+
+    fn read(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = self.position + i;
+            value |= u32::from((self.block[bit >> 3] >> (bit & 7)) & 1) << i;
+        }
+        self.position += count;
+        value
+    }
+
+    /// a handful of bc6h fields are stored with their bits in reverse order.
+    fn read_reversed(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read(1);
+        }
+        value
+    }
+}
+
+static WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+static WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+static WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weight(index: u32, bits: usize) -> u32 {
+    match bits {
+        2 => WEIGHTS2[index as usize],
+        3 => WEIGHTS3[index as usize],
+        _ => WEIGHTS4[index as usize],
+    }
+}
+
+/// two subset partitions as masks, a set bit puts the texel in the second subset.
+/// bc6h only uses the first 32.
+static PARTITIONS2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// the subset of every texel for the three subset partitions.
+static PARTITIONS3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// the texel whose index drops its top bit, for the second subset of a two subset partition.
+static ANCHORS2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// anchors for the second and third subsets of a three subset partition.
+#[rustfmt::skip]
+static ANCHORS3: [[usize; 2]; 64] = [
+    [3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+    [8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+    [3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+    [5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+    [8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+    [15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+    [3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+    [5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8],
+];
+
+fn subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => usize::from((PARTITIONS2[partition] >> texel) & 1 == 1),
+        _ => PARTITIONS3[partition][texel] as usize,
+    }
+}
+
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            1 => false,
+            2 => ANCHORS2[partition] == texel,
+            _ => ANCHORS3[partition].contains(&texel),
+        }
+}
+
+/// the layout of one of the eight bc7 modes.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize,
+}
+
+#[rustfmt::skip]
+static BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_pbits: false, shared_pbits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_pbits: true, shared_pbits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// decode one bc7 block in to 16 rgba texels, row by row.
+pub fn decode_bc7(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let mut texels = [[0u8; 4]; 16];
+    // the mode is the position of the first set bit, a block without one is reserved.
+    let mode = match (0..8).find(|m| block[0] & (1 << m) != 0) {
+        Some(mode) => mode,
+        None => return texels,
+    };
+    let info = &BC7_MODES[mode];
+
+    let mut bits = BitReader::new(block);
+    bits.read(mode + 1);
+    let partition = bits.read(info.partition_bits) as usize;
+    let rotation = bits.read(info.rotation_bits);
+    let index_selection = bits.read(info.index_selection_bits);
+
+    // every channel is stored for all endpoints before moving on to the next.
+    let endpoint_count = info.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = bits.read(info.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = bits.read(info.alpha_bits);
+    }
+
+    let mut pbits = [0u32; 6];
+    if info.endpoint_pbits {
+        for pbit in pbits.iter_mut().take(endpoint_count) {
+            *pbit = bits.read(1);
+        }
+    } else if info.shared_pbits {
+        for subset in 0..info.subsets {
+            let pbit = bits.read(1);
+            pbits[subset * 2] = pbit;
+            pbits[subset * 2 + 1] = pbit;
+        }
+    }
+    let has_pbits = info.endpoint_pbits || info.shared_pbits;
+
+    // expand every endpoint to 8 bits by replicating its top bits in to the bottom.
+    for (endpoint, &pbit) in endpoints.iter_mut().zip(pbits.iter()).take(endpoint_count) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let precision = if channel < 3 {
+                info.color_bits
+            } else {
+                info.alpha_bits
+            };
+            if precision == 0 {
+                *value = 255;
+                continue;
+            }
+            let (quantized, precision) = if has_pbits {
+                ((*value << 1) | pbit, precision + 1)
+            } else {
+                (*value, precision)
+            };
+            let expanded = quantized << (8 - precision);
+            *value = expanded | (expanded >> precision);
+        }
+    }
+
+    let mut indices = [0u32; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(info.subsets, partition, texel) as usize;
+        *index = bits.read(info.index_bits - anchor);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if info.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            let anchor = (texel == 0) as usize;
+            *index = bits.read(info.secondary_index_bits - anchor);
+        }
+    }
+
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let subset = subset(info.subsets, partition, texel);
+        let (low, high) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        // modes 4 and 5 interpolate color and alpha with separate indices,
+        // and mode 4 can swap which of the two is the wider one.
+        let primary = (indices[texel], info.index_bits);
+        let secondary = (secondary_indices[texel], info.secondary_index_bits);
+        let ((color_index, color_bits), (alpha_index, alpha_bits)) =
+            if info.secondary_index_bits == 0 {
+                (primary, primary)
+            } else if index_selection == 0 {
+                (primary, secondary)
+            } else {
+                (secondary, primary)
+            };
+
+        let mut color = [0u32; 4];
+        for channel in 0..4 {
+            let (index, bits) = if channel < 3 {
+                (color_index, color_bits)
+            } else {
+                (alpha_index, alpha_bits)
+            };
+            let w = weight(index, bits);
+            color[channel] = ((64 - w) * low[channel] + w * high[channel] + 32) >> 6;
+        }
+
+        if rotation > 0 {
+            color.swap(3, rotation as usize - 1);
+        }
+
+        for (o, c) in out.iter_mut().zip(color.iter()) {
+            *o = *c as u8;
+        }
+    }
+
+    texels
+}
+
+// bc6h endpoint fields, w and x are the first subset's endpoints, y and z the second's.
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+
+/// a run of bits in the bc6h header: the field it belongs to, the lowest bit it fills,
+/// how many bits it holds, and whether they are stored most significant first.
+type Field = (usize, usize, usize, bool);
+
+/// the layout of one of the fourteen bc6h modes.
+struct Bc6hMode {
+    /// the mode bits as they read out of the block.
+    id: u32,
+    transformed: bool,
+    subsets: usize,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    fields: &'static [Field],
+}
+
+#[rustfmt::skip]
+static BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { id: 0b00, transformed: true, subsets: 2, endpoint_bits: 10, delta_bits: [5, 5, 5], fields: &[
+        (GY, 4, 1, false), (BY, 4, 1, false), (BZ, 4, 1, false), (RW, 0, 10, false), (GW, 0, 10, false),
+        (BW, 0, 10, false), (RX, 0, 5, false), (GZ, 4, 1, false), (GY, 0, 4, false), (GX, 0, 5, false),
+        (BZ, 0, 1, false), (GZ, 0, 4, false), (BX, 0, 5, false), (BZ, 1, 1, false), (BY, 0, 4, false),
+        (RY, 0, 5, false), (BZ, 2, 1, false), (RZ, 0, 5, false), (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b01, transformed: true, subsets: 2, endpoint_bits: 7, delta_bits: [6, 6, 6], fields: &[
+        (GY, 5, 1, false), (GZ, 4, 1, false), (GZ, 5, 1, false), (RW, 0, 7, false), (BZ, 0, 1, false),
+        (BZ, 1, 1, false), (BY, 4, 1, false), (GW, 0, 7, false), (BY, 5, 1, false), (BZ, 2, 1, false),
+        (GY, 4, 1, false), (BW, 0, 7, false), (BZ, 3, 1, false), (BZ, 5, 1, false), (BZ, 4, 1, false),
+        (RX, 0, 6, false), (GY, 0, 4, false), (GX, 0, 6, false), (GZ, 0, 4, false), (BX, 0, 6, false),
+        (BY, 0, 4, false), (RY, 0, 6, false), (RZ, 0, 6, false),
+    ] },
+    Bc6hMode { id: 0b00010, transformed: true, subsets: 2, endpoint_bits: 11, delta_bits: [5, 4, 4], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 5, false), (RW, 10, 1, false),
+        (GY, 0, 4, false), (GX, 0, 4, false), (GW, 10, 1, false), (BZ, 0, 1, false), (GZ, 0, 4, false),
+        (BX, 0, 4, false), (BW, 10, 1, false), (BZ, 1, 1, false), (BY, 0, 4, false), (RY, 0, 5, false),
+        (BZ, 2, 1, false), (RZ, 0, 5, false), (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b00110, transformed: true, subsets: 2, endpoint_bits: 11, delta_bits: [4, 5, 4], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 4, false), (RW, 10, 1, false),
+        (GZ, 4, 1, false), (GY, 0, 4, false), (GX, 0, 5, false), (GW, 10, 1, false), (GZ, 0, 4, false),
+        (BX, 0, 4, false), (BW, 10, 1, false), (BZ, 1, 1, false), (BY, 0, 4, false), (RY, 0, 4, false),
+        (BZ, 0, 1, false), (BZ, 2, 1, false), (RZ, 0, 4, false), (GY, 4, 1, false), (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b01010, transformed: true, subsets: 2, endpoint_bits: 11, delta_bits: [4, 4, 5], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 4, false), (RW, 10, 1, false),
+        (BY, 4, 1, false), (GY, 0, 4, false), (GX, 0, 4, false), (GW, 10, 1, false), (BZ, 0, 1, false),
+        (GZ, 0, 4, false), (BX, 0, 5, false), (BW, 10, 1, false), (BY, 0, 4, false), (RY, 0, 4, false),
+        (BZ, 1, 1, false), (BZ, 2, 1, false), (RZ, 0, 4, false), (BZ, 4, 1, false), (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b01110, transformed: true, subsets: 2, endpoint_bits: 9, delta_bits: [5, 5, 5], fields: &[
+        (RW, 0, 9, false), (BY, 4, 1, false), (GW, 0, 9, false), (GY, 4, 1, false), (BW, 0, 9, false),
+        (BZ, 4, 1, false), (RX, 0, 5, false), (GZ, 4, 1, false), (GY, 0, 4, false), (GX, 0, 5, false),
+        (BZ, 0, 1, false), (GZ, 0, 4, false), (BX, 0, 5, false), (BZ, 1, 1, false), (BY, 0, 4, false),
+        (RY, 0, 5, false), (BZ, 2, 1, false), (RZ, 0, 5, false), (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b10010, transformed: true, subsets: 2, endpoint_bits: 8, delta_bits: [6, 5, 5], fields: &[
+        (RW, 0, 8, false), (GZ, 4, 1, false), (BY, 4, 1, false), (GW, 0, 8, false), (BZ, 2, 1, false),
+        (GY, 4, 1, false), (BW, 0, 8, false), (BZ, 3, 1, false), (BZ, 4, 1, false), (RX, 0, 6, false),
+        (GY, 0, 4, false), (GX, 0, 5, false), (BZ, 0, 1, false), (GZ, 0, 4, false), (BX, 0, 5, false),
+        (BZ, 1, 1, false), (BY, 0, 4, false), (RY, 0, 6, false), (RZ, 0, 6, false),
+    ] },
+    Bc6hMode { id: 0b10110, transformed: true, subsets: 2, endpoint_bits: 8, delta_bits: [5, 6, 5], fields: &[
+        (RW, 0, 8, false), (BZ, 0, 1, false), (BY, 4, 1, false), (GW, 0, 8, false), (GY, 5, 1, false),
+        (GY, 4, 1, false), (BW, 0, 8, false), (GZ, 5, 1, false), (BZ, 4, 1, false), (RX, 0, 5, false),
+        (GZ, 4, 1, false), (GY, 0, 4, false), (GX, 0, 6, false), (GZ, 0, 4, false), (BX, 0, 5, false),
+        (BZ, 1, 1, false), (BY, 0, 4, false), (RY, 0, 5, false), (BZ, 2, 1, false), (RZ, 0, 5, false),
+        (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b11010, transformed: true, subsets: 2, endpoint_bits: 8, delta_bits: [5, 5, 6], fields: &[
+        (RW, 0, 8, false), (BZ, 1, 1, false), (BY, 4, 1, false), (GW, 0, 8, false), (BY, 5, 1, false),
+        (GY, 4, 1, false), (BW, 0, 8, false), (BZ, 5, 1, false), (BZ, 4, 1, false), (RX, 0, 5, false),
+        (GZ, 4, 1, false), (GY, 0, 4, false), (GX, 0, 5, false), (BZ, 0, 1, false), (GZ, 0, 4, false),
+        (BX, 0, 6, false), (BY, 0, 4, false), (RY, 0, 5, false), (BZ, 2, 1, false), (RZ, 0, 5, false),
+        (BZ, 3, 1, false),
+    ] },
+    Bc6hMode { id: 0b11110, transformed: false, subsets: 2, endpoint_bits: 6, delta_bits: [6, 6, 6], fields: &[
+        (RW, 0, 6, false), (GZ, 4, 1, false), (BZ, 0, 1, false), (BZ, 1, 1, false), (BY, 4, 1, false),
+        (GW, 0, 6, false), (GY, 5, 1, false), (BY, 5, 1, false), (BZ, 2, 1, false), (GY, 4, 1, false),
+        (BW, 0, 6, false), (GZ, 5, 1, false), (BZ, 3, 1, false), (BZ, 5, 1, false), (BZ, 4, 1, false),
+        (RX, 0, 6, false), (GY, 0, 4, false), (GX, 0, 6, false), (GZ, 0, 4, false), (BX, 0, 6, false),
+        (BY, 0, 4, false), (RY, 0, 6, false), (RZ, 0, 6, false),
+    ] },
+    Bc6hMode { id: 0b00011, transformed: false, subsets: 1, endpoint_bits: 10, delta_bits: [10, 10, 10], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 10, false), (GX, 0, 10, false),
+        (BX, 0, 10, false),
+    ] },
+    Bc6hMode { id: 0b00111, transformed: true, subsets: 1, endpoint_bits: 11, delta_bits: [9, 9, 9], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 9, false), (RW, 10, 1, false),
+        (GX, 0, 9, false), (GW, 10, 1, false), (BX, 0, 9, false), (BW, 10, 1, false),
+    ] },
+    Bc6hMode { id: 0b01011, transformed: true, subsets: 1, endpoint_bits: 12, delta_bits: [8, 8, 8], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 8, false), (RW, 10, 2, true),
+        (GX, 0, 8, false), (GW, 10, 2, true), (BX, 0, 8, false), (BW, 10, 2, true),
+    ] },
+    Bc6hMode { id: 0b01111, transformed: true, subsets: 1, endpoint_bits: 16, delta_bits: [4, 4, 4], fields: &[
+        (RW, 0, 10, false), (GW, 0, 10, false), (BW, 0, 10, false), (RX, 0, 4, false), (RW, 10, 6, true),
+        (GX, 0, 4, false), (GW, 10, 6, true), (BX, 0, 4, false), (BW, 10, 6, true),
+    ] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 || value == 0 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    } else if bits >= 15 || value == 0 {
+        value
+    } else if value == (1 << bits) - 1 {
+        0xffff
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+/// scale an interpolated value back down in to the bit pattern of a half float.
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        (((-value * 31) >> 5) as u16) | 0x8000
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+/// decode one bc6h block in to 16 rgb texels stored as half float bits, row by row.
+pub fn decode_bc6h(block: &[u8; 16], signed: bool) -> [[u16; 3]; 16] {
+    let mut texels = [[0u16; 3]; 16];
+
+    let mut bits = BitReader::new(block);
+    let mut id = bits.read(2);
+    if id > 1 {
+        id |= bits.read(3) << 2;
+    }
+    let info = match BC6H_MODES.iter().find(|mode| mode.id == id) {
+        Some(info) => info,
+        None => return texels,
+    };
+
+    let mut fields = [0i32; 12];
+    for &(field, low, count, reversed) in info.fields {
+        let value = if reversed {
+            bits.read_reversed(count)
+        } else {
+            bits.read(count)
+        };
+        fields[field] |= (value << low) as i32;
+    }
+    let partition = if info.subsets == 2 {
+        bits.read(5) as usize
+    } else {
+        0
+    };
+
+    let endpoint_count = info.subsets * 2;
+    let mask = (1 << info.endpoint_bits) - 1;
+    let mut endpoints = [[0i32; 3]; 4];
+    for (i, endpoint) in endpoints.iter_mut().take(endpoint_count).enumerate() {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            *value = fields[i * 3 + channel];
+        }
+    }
+    if signed {
+        for value in endpoints[0].iter_mut() {
+            *value = sign_extend(*value, info.endpoint_bits);
+        }
+    }
+    // transformed modes store the other endpoints as deltas from the first.
+    for i in 1..endpoint_count {
+        for channel in 0..3 {
+            let value = endpoints[i][channel];
+            endpoints[i][channel] = if info.transformed {
+                let value = sign_extend(value, info.delta_bits[channel]);
+                let value = (endpoints[0][channel] + value) & mask;
+                if signed {
+                    sign_extend(value, info.endpoint_bits)
+                } else {
+                    value
+                }
+            } else if signed {
+                sign_extend(value, info.endpoint_bits)
+            } else {
+                value
+            };
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = unquantize(*value, info.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if info.subsets == 2 { 3 } else { 4 };
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let anchor = is_anchor(info.subsets, partition, texel) as usize;
+        let w = weight(bits.read(index_bits - anchor), index_bits) as i32;
+        let subset = subset(info.subsets, partition, texel);
+        let (low, high) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        for channel in 0..3 {
+            let value = ((64 - w) * low[channel] + w * high[channel] + 32) >> 6;
+            out[channel] = finish_unquantize(value, signed);
+        }
+    }
+
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the blocks below were packed by hand from the format spec, and the expected
+    // texels worked out from its interpolation and unquantization formulas.
+    // every block puts index i in texel i, so the texels walk through all 16 weights.
+
+    #[test]
+    fn bc7_mode_6() {
+        // r 0 -> 127, g 127 -> 0, b and a constant, p bits 0 and 1.
+        let block = [
+            0x40, 0xc0, 0xff, 0x0f, 0x00, 0x02, 0xff, 0x7f, 0x11, 0x32, 0x54, 0x76, 0x98, 0xba,
+            0xdc, 0xfe,
+        ];
+        let red = [
+            0, 16, 36, 52, 68, 84, 104, 120, 135, 151, 171, 187, 203, 219, 239, 255,
+        ];
+        let green = [
+            254, 238, 218, 203, 187, 171, 151, 135, 120, 104, 84, 68, 52, 37, 17, 1,
+        ];
+
+        let texels = decode_bc7(&block);
+        for (i, texel) in texels.iter().enumerate() {
+            // the p bit splits the constant channels between the two endpoints.
+            let (blue, alpha) = if i < 8 { (128, 254) } else { (129, 255) };
+            assert_eq!(*texel, [red[i], green[i], blue, alpha], "texel {}", i);
+        }
+    }
+
+    #[test]
+    fn bc7_reserved_mode() {
+        assert_eq!(decode_bc7(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn bc6h_unsigned() {
+        // mode 11, r 0 -> 1023, g 512, b 0.
+        let block = [
+            0x03, 0x00, 0x00, 0x01, 0xf8, 0x1f, 0x40, 0x00, 0x10, 0x32, 0x54, 0x76, 0x98, 0xba,
+            0xdc, 0xfe,
+        ];
+        let red = [
+            0x0000, 0x07c0, 0x1170, 0x1930, 0x20f0, 0x28b0, 0x3260, 0x3a20, 0x41df, 0x499f, 0x534f,
+            0x5b0f, 0x62cf, 0x6a8f, 0x743f, 0x7bff,
+        ];
+
+        let texels = decode_bc6h(&block, false);
+        for (i, texel) in texels.iter().enumerate() {
+            assert_eq!(*texel, [red[i], 0x3e0f, 0x0000], "texel {}", i);
+        }
+    }
+
+    #[test]
+    fn bc6h_signed() {
+        // mode 11, r -512 -> 511 (both saturate), g 1, b -1.
+        let block = [
+            0x03, 0xc0, 0x00, 0xfe, 0xff, 0x2f, 0x80, 0xff, 0x11, 0x32, 0x54, 0x76, 0x98, 0xba,
+            0xdc, 0xfe,
+        ];
+        let red = [
+            0xfbff, 0xec7f, 0xd91f, 0xc99f, 0xba20, 0xaaa0, 0x9740, 0x87c0, 0x07c0, 0x1740, 0x2aa0,
+            0x3a20, 0x499f, 0x591f, 0x6c7f, 0x7bff,
+        ];
+
+        let texels = decode_bc6h(&block, true);
+        for (i, texel) in texels.iter().enumerate() {
+            assert_eq!(*texel, [red[i], 0x005d, 0x805d], "texel {}", i);
+        }
+    }
+
+    #[test]
+    fn bc6h_reserved_mode() {
+        // 0b10011 isn't one of the fourteen modes.
+        let mut block = [0xff; 16];
+        block[0] = 0b1_0011;
+        assert_eq!(decode_bc6h(&block, false), [[0; 3]; 16]);
+    }
+}
@@ -0,0 +1,775 @@
+use super::{bc, encode_srgb};
+use image::RgbaImage;
+use std::cmp;
+use std::mem;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::instance::PhysicalDevice;
+use vulkano::VulkanObject;
+
+#[derive(Debug, PartialEq)]
+pub enum ContainerError {
+    /// the file doesn't start with a dds or ktx2 identifier.
+    BadMagic,
+    /// the header or the image data runs past the end of the file.
+    Truncated,
+    /// the texture is valid but isn't a square cubemap.
+    NotACubemap,
+    /// a dxgi, fourcc or vulkan format code we can't load.
+    UnsupportedFormat(u32),
+    /// ktx2 supercompression (basis, zstd, ...) isn't supported.
+    Supercompressed(u32),
+    /// the faces are wider than `MAX_FACE_SIZE`.
+    TooLarge(u32),
+    /// more mip levels than a face of this size can have.
+    TooManyLevels(u32),
+}
+
+/// the texel formats we know how to upload, or convert when the device can't sample them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PixelFormat {
+    Rgba8Unorm,
+    Rgba8Srgb,
+    Rgba16Float,
+    Rgba32Float,
+    Bc6hUfloat,
+    Bc6hSfloat,
+    Bc7Unorm,
+    Bc7Srgb,
+}
+
+impl PixelFormat {
+    pub fn vk_format(self) -> Format {
+        match self {
+            PixelFormat::Rgba8Unorm => Format::R8G8B8A8Unorm,
+            PixelFormat::Rgba8Srgb => Format::R8G8B8A8Srgb,
+            PixelFormat::Rgba16Float => Format::R16G16B16A16Sfloat,
+            PixelFormat::Rgba32Float => Format::R32G32B32A32Sfloat,
+            PixelFormat::Bc6hUfloat => Format::BC6HUfloatBlock,
+            PixelFormat::Bc6hSfloat => Format::BC6HSfloatBlock,
+            PixelFormat::Bc7Unorm => Format::BC7UnormBlock,
+            PixelFormat::Bc7Srgb => Format::BC7SrgbBlock,
+        }
+    }
+
+    pub fn is_compressed(self) -> bool {
+        match self {
+            PixelFormat::Bc6hUfloat
+            | PixelFormat::Bc6hSfloat
+            | PixelFormat::Bc7Unorm
+            | PixelFormat::Bc7Srgb => true,
+            _ => false,
+        }
+    }
+
+    /// the `VkFormat` value, as stored in a ktx2 header.
+    fn vk_code(self) -> u32 {
+        match self {
+            PixelFormat::Rgba8Unorm => 37,
+            PixelFormat::Rgba8Srgb => 43,
+            PixelFormat::Rgba16Float => 97,
+            PixelFormat::Rgba32Float => 109,
+            PixelFormat::Bc6hUfloat => 143,
+            PixelFormat::Bc6hSfloat => 144,
+            PixelFormat::Bc7Unorm => 145,
+            PixelFormat::Bc7Srgb => 146,
+        }
+    }
+
+    /// whether the device was created able to sample this format with the skybox's linear
+    /// filtering. the bc feature only promises the formats exist and rgba32f doesn't have
+    /// to filter at all, so the driver is asked about every format.
+    pub fn is_supported(self, device: &Device) -> bool {
+        (!self.is_compressed() || device.enabled_features().texture_compression_bc)
+            && can_sample(device.physical_device(), self.vk_code())
+    }
+
+    /// the size of one face of a mip level `size` texels wide.
+    pub fn face_bytes(self, size: u32) -> usize {
+        let size = size as usize;
+        match self {
+            PixelFormat::Rgba8Unorm | PixelFormat::Rgba8Srgb => size * size * 4,
+            PixelFormat::Rgba16Float => size * size * 8,
+            PixelFormat::Rgba32Float => size * size * 16,
+            // 16 bytes for every 4x4 block, partial blocks are padded out.
+            _ => {
+                let blocks = (size + 3) / 4;
+                blocks * blocks * 16
+            }
+        }
+    }
+
+    /// what a compressed format turns in to when we decode it on the cpu.
+    fn decompressed(self) -> PixelFormat {
+        match self {
+            PixelFormat::Bc6hUfloat | PixelFormat::Bc6hSfloat => PixelFormat::Rgba16Float,
+            PixelFormat::Bc7Unorm => PixelFormat::Rgba8Unorm,
+            PixelFormat::Bc7Srgb => PixelFormat::Rgba8Srgb,
+            other => other,
+        }
+    }
+
+    fn from_dxgi(code: u32) -> Result<Self, ContainerError> {
+        Ok(match code {
+            2 => PixelFormat::Rgba32Float,
+            10 => PixelFormat::Rgba16Float,
+            28 => PixelFormat::Rgba8Unorm,
+            29 => PixelFormat::Rgba8Srgb,
+            95 => PixelFormat::Bc6hUfloat,
+            96 => PixelFormat::Bc6hSfloat,
+            98 => PixelFormat::Bc7Unorm,
+            99 => PixelFormat::Bc7Srgb,
+            other => return Err(ContainerError::UnsupportedFormat(other)),
+        })
+    }
+
+    fn from_vk(code: u32) -> Result<Self, ContainerError> {
+        Ok(match code {
+            37 => PixelFormat::Rgba8Unorm,
+            43 => PixelFormat::Rgba8Srgb,
+            97 => PixelFormat::Rgba16Float,
+            109 => PixelFormat::Rgba32Float,
+            143 => PixelFormat::Bc6hUfloat,
+            144 => PixelFormat::Bc6hSfloat,
+            145 => PixelFormat::Bc7Unorm,
+            146 => PixelFormat::Bc7Srgb,
+            other => return Err(ContainerError::UnsupportedFormat(other)),
+        })
+    }
+}
+
+/// whether optimally tiled images of format `code` can be sampled with linear filtering
+/// on `physical`.
+/// vulkano 0.11 has no wrapper for format properties, so this goes to the driver directly.
+fn can_sample(physical: PhysicalDevice, code: u32) -> bool {
+    let instance = physical.instance();
+    unsafe {
+        let mut properties: vk_sys::FormatProperties = mem::zeroed();
+        instance.pointers().GetPhysicalDeviceFormatProperties(
+            physical.internal_object(),
+            code,
+            &mut properties,
+        );
+        let wanted = vk_sys::FORMAT_FEATURE_SAMPLED_IMAGE_BIT
+            | vk_sys::FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT;
+        properties.optimalTilingFeatures & wanted == wanted
+    }
+}
+
+/// a cubemap straight out of a texture container, every mip level included.
+#[derive(Clone)]
+pub struct Cubemap {
+    pub format: PixelFormat,
+    pub face_size: u32,
+    /// one entry per mip level, largest first, each holding the six faces
+    /// back to back in +X, -X, +Y, -Y, +Z, -Z order.
+    pub levels: Vec<Vec<u8>>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ContainerError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| {
+            u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+        })
+        .ok_or(ContainerError::Truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<usize, ContainerError> {
+    let low = u64::from(read_u32(bytes, offset)?);
+    let high = u64::from(read_u32(bytes, offset + 4)?);
+    Ok((low | high << 32) as usize)
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], ContainerError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or(ContainerError::Truncated)
+}
+
+fn mip_size(face_size: u32, level: usize) -> u32 {
+    cmp::max(face_size >> level, 1)
+}
+
+/// the largest face we'll load, well past what any device can make a cubemap of,
+/// but small enough that the size of a whole level can't overflow.
+pub const MAX_FACE_SIZE: u32 = 16384;
+
+/// check the face size and mip count from a header before anything is allocated for them.
+/// a chain can't go past the 1x1 level, which is log2(size) + 1 levels in.
+fn check_levels(face_size: u32, count: u32) -> Result<usize, ContainerError> {
+    if face_size == 0 {
+        return Err(ContainerError::NotACubemap);
+    }
+    if face_size > MAX_FACE_SIZE {
+        return Err(ContainerError::TooLarge(face_size));
+    }
+    if count > 32 - face_size.leading_zeros() {
+        return Err(ContainerError::TooManyLevels(count));
+    }
+    Ok(count as usize)
+}
+
+static DDS_MAGIC: &[u8] = b"DDS ";
+static KTX2_MAGIC: &[u8] = &[
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+// dds header flags and bits we care about.
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFE00;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const FOURCC_DX10: u32 = 0x3031_5844;
+// legacy d3d9 format codes that sneak in to the fourcc field.
+const D3DFMT_A16B16G16R16F: u32 = 113;
+const D3DFMT_A32B32G32R32F: u32 = 116;
+
+impl Cubemap {
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(DDS_MAGIC) || bytes.starts_with(KTX2_MAGIC)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContainerError> {
+        if bytes.starts_with(DDS_MAGIC) {
+            Self::from_dds(bytes)
+        } else if bytes.starts_with(KTX2_MAGIC) {
+            Self::from_ktx2(bytes)
+        } else {
+            Err(ContainerError::BadMagic)
+        }
+    }
+
+    /// dds stores every mip of a face before moving on to the next face.
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, ContainerError> {
+        if !bytes.starts_with(DDS_MAGIC) {
+            return Err(ContainerError::BadMagic);
+        }
+        let flags = read_u32(bytes, 8)?;
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+            cmp::max(read_u32(bytes, 28)?, 1)
+        } else {
+            1
+        };
+        let pixel_flags = read_u32(bytes, 80)?;
+        let fourcc = read_u32(bytes, 84)?;
+        let caps2 = read_u32(bytes, 112)?;
+
+        let mut swizzle_bgra = false;
+        let (format, is_cube, mut offset) =
+            if pixel_flags & DDPF_FOURCC != 0 && fourcc == FOURCC_DX10 {
+                let dxgi = read_u32(bytes, 128)?;
+                let misc = read_u32(bytes, 136)?;
+                // the bgra variants are common enough out of photo tools to be worth a swizzle.
+                let format = match dxgi {
+                    87 => {
+                        swizzle_bgra = true;
+                        PixelFormat::Rgba8Unorm
+                    }
+                    91 => {
+                        swizzle_bgra = true;
+                        PixelFormat::Rgba8Srgb
+                    }
+                    other => PixelFormat::from_dxgi(other)?,
+                };
+                (format, misc & DDS_RESOURCE_MISC_TEXTURECUBE != 0, 148)
+            } else {
+                let format = if pixel_flags & DDPF_FOURCC != 0 {
+                    match fourcc {
+                        D3DFMT_A16B16G16R16F => PixelFormat::Rgba16Float,
+                        D3DFMT_A32B32G32R32F => PixelFormat::Rgba32Float,
+                        other => return Err(ContainerError::UnsupportedFormat(other)),
+                    }
+                } else if pixel_flags & DDPF_RGB != 0 && read_u32(bytes, 88)? == 32 {
+                    // legacy headers can't say whether they're srgb, colour maps nearly always are.
+                    swizzle_bgra = read_u32(bytes, 92)? == 0x00FF_0000;
+                    PixelFormat::Rgba8Srgb
+                } else {
+                    return Err(ContainerError::UnsupportedFormat(pixel_flags));
+                };
+                (
+                    format,
+                    caps2 & DDSCAPS2_CUBEMAP_ALL_FACES == DDSCAPS2_CUBEMAP_ALL_FACES,
+                    128,
+                )
+            };
+
+        if !is_cube || width != height {
+            return Err(ContainerError::NotACubemap);
+        }
+        let mip_count = check_levels(width, mip_count)?;
+
+        let mut levels = vec![Vec::new(); mip_count];
+        for _face in 0..6 {
+            for (level, data) in levels.iter_mut().enumerate() {
+                let len = format.face_bytes(mip_size(width, level));
+                data.extend_from_slice(slice(bytes, offset, len)?);
+                offset += len;
+            }
+        }
+
+        if swizzle_bgra {
+            for level in levels.iter_mut() {
+                for texel in level.chunks_mut(4) {
+                    texel.swap(0, 2);
+                }
+            }
+        }
+
+        Ok(Cubemap {
+            format,
+            face_size: width,
+            levels,
+        })
+    }
+
+    /// ktx2 keeps an index of levels, each holding every face of every layer.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, ContainerError> {
+        if !bytes.starts_with(KTX2_MAGIC) {
+            return Err(ContainerError::BadMagic);
+        }
+        let format = PixelFormat::from_vk(read_u32(bytes, 12)?)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let depth = read_u32(bytes, 28)?;
+        let faces = read_u32(bytes, 36)?;
+        // zero levels asks the loader to generate them, we just use the one that's there.
+        let level_count = cmp::max(read_u32(bytes, 40)?, 1);
+        let supercompression = read_u32(bytes, 44)?;
+
+        if supercompression != 0 {
+            return Err(ContainerError::Supercompressed(supercompression));
+        }
+        if faces != 6 || width != height || depth > 1 {
+            return Err(ContainerError::NotACubemap);
+        }
+        let level_count = check_levels(width, level_count)?;
+
+        // the level index starts right after the fixed size header.
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry = 80 + level * 24;
+                let offset = read_u64(bytes, entry)?;
+                // cubemap arrays only contribute their first cube.
+                let len = format.face_bytes(mip_size(width, level)) * 6;
+                slice(bytes, offset, len).map(|data| data.to_vec())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Cubemap {
+            format,
+            face_size: width,
+            levels,
+        })
+    }
+
+    /// wrap six rgba faces as a single level cubemap.
+    pub fn from_faces(faces: &[RgbaImage]) -> Self {
+        let levels = vec![faces
+            .iter()
+            .flat_map(|face| face.iter().cloned())
+            .collect::<Vec<u8>>()];
+
+        Cubemap {
+            format: PixelFormat::Rgba8Srgb,
+            face_size: faces[0].width(),
+            levels,
+        }
+    }
+
+    /// decode block compressed levels on the cpu, for devices without `textureCompressionBC`.
+    /// bc7 becomes rgba8 and bc6h becomes rgba16f, uncompressed cubemaps are returned as is.
+    pub fn decompress(&self) -> Cubemap {
+        if !self.format.is_compressed() {
+            return self.clone();
+        }
+        let format = self.format.decompressed();
+
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let size = mip_size(self.face_size, level) as usize;
+                let blocks_wide = (size + 3) / 4;
+                let texel_bytes = format.face_bytes(1);
+                let mut out = vec![0u8; format.face_bytes(size as u32) * 6];
+
+                for (face, face_data) in
+                    data.chunks(self.format.face_bytes(size as u32)).enumerate()
+                {
+                    let face_out = &mut out[face * size * size * texel_bytes..];
+                    for (i, block) in face_data.chunks(16).enumerate() {
+                        let mut bytes = [0u8; 16];
+                        bytes.copy_from_slice(block);
+                        let texels = self.decode_block(&bytes);
+
+                        let (block_x, block_y) = ((i % blocks_wide) * 4, (i / blocks_wide) * 4);
+                        for (t, texel) in texels.iter().enumerate() {
+                            let (x, y) = (block_x + t % 4, block_y + t / 4);
+                            // small mips only use the top left of their block.
+                            if x < size && y < size {
+                                let at = (y * size + x) * texel_bytes;
+                                face_out[at..at + texel_bytes].copy_from_slice(texel);
+                            }
+                        }
+                    }
+                }
+                out
+            })
+            .collect();
+
+        Cubemap {
+            format,
+            face_size: self.face_size,
+            levels,
+        }
+    }
+
+    /// what to upload when the device can't sample `format`. block compressed levels are
+    /// decoded and 32 bit floats are narrowed to half floats, which every device filters.
+    pub fn fallback(&self) -> Cubemap {
+        if self.format != PixelFormat::Rgba32Float {
+            return self.decompress();
+        }
+
+        let levels = self
+            .levels
+            .iter()
+            .map(|data| {
+                data.chunks(4)
+                    .flat_map(|c| {
+                        let float = f32::from_bits(
+                            u32::from(c[0])
+                                | u32::from(c[1]) << 8
+                                | u32::from(c[2]) << 16
+                                | u32::from(c[3]) << 24,
+                        );
+                        let half = f32_to_half(float);
+                        vec![half as u8, (half >> 8) as u8]
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Cubemap {
+            format: PixelFormat::Rgba16Float,
+            face_size: self.face_size,
+            levels,
+        }
+    }
+
+    /// one block of texels, each in the byte layout of the decompressed format.
+    fn decode_block(&self, block: &[u8; 16]) -> Vec<Vec<u8>> {
+        match self.format {
+            PixelFormat::Bc7Unorm | PixelFormat::Bc7Srgb => bc::decode_bc7(block)
+                .iter()
+                .map(|texel| texel.to_vec())
+                .collect(),
+            _ => {
+                let signed = self.format == PixelFormat::Bc6hSfloat;
+                bc::decode_bc6h(block, signed)
+                    .iter()
+                    .map(|texel| {
+                        // half float 1.0 for alpha.
+                        [texel[0], texel[1], texel[2], 0x3C00]
+                            .iter()
+                            .flat_map(|half| vec![*half as u8, (*half >> 8) as u8])
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// the top mip as six 8 bit srgb images, for things like the irradiance projection
+    /// that want plain rgba faces. hdr values are clamped.
+    pub fn to_faces(&self) -> Vec<RgbaImage> {
+        let decompressed = self.decompress();
+        let size = self.face_size;
+        let face_bytes = decompressed.format.face_bytes(size);
+
+        decompressed.levels[0]
+            .chunks(face_bytes)
+            .map(|face| {
+                let texels: Vec<u8> = match decompressed.format {
+                    PixelFormat::Rgba8Srgb => face.to_vec(),
+                    PixelFormat::Rgba8Unorm => face
+                        .chunks(4)
+                        .flat_map(|t| {
+                            let linear = |c: u8| f32::from(c) / 255.0;
+                            encode_rgba([linear(t[0]), linear(t[1]), linear(t[2]), linear(t[3])])
+                        })
+                        .collect(),
+                    PixelFormat::Rgba16Float => face
+                        .chunks(8)
+                        .flat_map(|t| {
+                            let half =
+                                |i: usize| half_to_f32(u16::from(t[i]) | u16::from(t[i + 1]) << 8);
+                            encode_rgba([half(0), half(2), half(4), half(6)])
+                        })
+                        .collect(),
+                    _ => face
+                        .chunks(16)
+                        .flat_map(|t| {
+                            let float = |i: usize| {
+                                f32::from_bits(
+                                    u32::from(t[i])
+                                        | u32::from(t[i + 1]) << 8
+                                        | u32::from(t[i + 2]) << 16
+                                        | u32::from(t[i + 3]) << 24,
+                                )
+                            };
+                            encode_rgba([float(0), float(4), float(8), float(12)])
+                        })
+                        .collect(),
+                };
+                RgbaImage::from_raw(size, size, texels).unwrap()
+            })
+            .collect()
+    }
+}
+
+/// linear rgba to 8 bit srgb, alpha stays linear.
+fn encode_rgba(color: [f32; 4]) -> Vec<u8> {
+    vec![
+        encode_srgb(color[0]),
+        encode_srgb(color[1]),
+        encode_srgb(color[2]),
+        (color[3].max(0.0).min(1.0) * 255.0).round() as u8,
+    ]
+}
+
+/// rounds to the nearest half float, ties to even. values past 65504 become infinity
+/// and ones too small for a normal half become subnormals or zero.
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    // drop the low `shift` bits, rounding to nearest with ties to even.
+    let round = |bits: u32, shift: u32| {
+        let kept = bits >> shift;
+        let rest = bits & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if rest > halfway || (rest == halfway && kept & 1 != 0) {
+            kept + 1
+        } else {
+            kept
+        }
+    };
+
+    if exponent == 0xFF {
+        // infinity stays infinity, any nan becomes a quiet one.
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 31 {
+        return sign | 0x7C00;
+    }
+    if exponent <= 0 {
+        // anything under half the smallest subnormal rounds to zero.
+        if exponent < -10 {
+            return sign;
+        }
+        // rounding up out of the subnormals lands on the smallest normal, as it should.
+        return sign | round(mantissa | 0x80_0000, (14 - exponent) as u32) as u16;
+    }
+    // a carry out of the mantissa bumps the exponent, up to infinity if it has to.
+    sign | round((exponent as u32) << 23 | mantissa, 13) as u16
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((half >> 10) & 0x1F);
+    let mantissa = f32::from(half & 0x3FF);
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 => sign * std::f32::INFINITY,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(bytes: &mut [u8], offset: usize, value: u64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// a legacy rgba8 dds cubemap header, followed by `data` bytes of zeroed texels.
+    fn dds(size: u32, mips: u32, data: usize) -> Vec<u8> {
+        let mut bytes = vec![0; 128 + data];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        put_u32(&mut bytes, 4, 124);
+        put_u32(&mut bytes, 8, DDSD_MIPMAPCOUNT);
+        put_u32(&mut bytes, 12, size);
+        put_u32(&mut bytes, 16, size);
+        put_u32(&mut bytes, 28, mips);
+        put_u32(&mut bytes, 80, DDPF_RGB);
+        put_u32(&mut bytes, 88, 32);
+        put_u32(&mut bytes, 92, 0xFF);
+        put_u32(&mut bytes, 112, DDSCAPS2_CUBEMAP_ALL_FACES);
+        bytes
+    }
+
+    /// an rgba8 ktx2 cubemap header with a level index but no level data.
+    fn ktx2(size: u32, levels: u32) -> Vec<u8> {
+        let mut bytes = vec![0; 80 + 24 * levels as usize];
+        bytes[..12].copy_from_slice(KTX2_MAGIC);
+        put_u32(&mut bytes, 12, 37);
+        put_u32(&mut bytes, 20, size);
+        put_u32(&mut bytes, 24, size);
+        put_u32(&mut bytes, 36, 6);
+        put_u32(&mut bytes, 40, levels);
+        bytes
+    }
+
+    #[test]
+    fn dds_levels() {
+        // 4x4, 2x2 and 1x1 rgba8 faces, six of each.
+        let cubemap = Cubemap::from_bytes(&dds(4, 3, 6 * (64 + 16 + 4))).unwrap();
+        assert_eq!(cubemap.format, PixelFormat::Rgba8Srgb);
+        assert_eq!(cubemap.face_size, 4);
+        let sizes: Vec<usize> = cubemap.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![6 * 64, 6 * 16, 6 * 4]);
+    }
+
+    #[test]
+    fn truncated_header() {
+        let bytes = dds(4, 1, 6 * 64);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes[..64]).err(),
+            Some(ContainerError::Truncated)
+        );
+        let bytes = ktx2(4, 1);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes[..42]).err(),
+            Some(ContainerError::Truncated)
+        );
+    }
+
+    #[test]
+    fn truncated_data() {
+        let bytes = dds(4, 3, 6 * (64 + 16 + 4) - 1);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::Truncated)
+        );
+        let bytes = ktx2(4, 1);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::Truncated)
+        );
+    }
+
+    #[test]
+    fn too_many_levels() {
+        // a 4x4 face only has three levels.
+        let bytes = dds(4, 4, 0);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::TooManyLevels(4))
+        );
+        let bytes = dds(4, u32::max_value(), 0);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::TooManyLevels(u32::max_value()))
+        );
+        let bytes = ktx2(4, 4);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::TooManyLevels(4))
+        );
+    }
+
+    #[test]
+    fn too_large() {
+        let size = MAX_FACE_SIZE + 1;
+        let bytes = dds(size, 1, 0);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::TooLarge(size))
+        );
+        let bytes = ktx2(size, 1);
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::TooLarge(size))
+        );
+    }
+
+    #[test]
+    fn half_rounding() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3C00);
+        assert_eq!(f32_to_half(-2.0), 0xC000);
+        assert_eq!(f32_to_half(65504.0), 0x7BFF);
+        // ties go to the even mantissa.
+        assert_eq!(f32_to_half(1.0 + 2f32.powi(-11)), 0x3C00);
+        assert_eq!(f32_to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3C02);
+        // halfway between 65504 and the next step up rounds to even, which is infinity.
+        assert_eq!(f32_to_half(65520.0), 0x7C00);
+        assert_eq!(f32_to_half(1.0e6), 0x7C00);
+        assert_eq!(f32_to_half(-std::f32::INFINITY), 0xFC00);
+        assert_eq!(f32_to_half(std::f32::NAN) & 0x7E00, 0x7E00);
+        // subnormals, and the edges of them.
+        assert_eq!(f32_to_half(2f32.powi(-14)), 0x0400);
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(3.0 * 2f32.powi(-24)), 0x0003);
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(1.5 * 2f32.powi(-25)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-14) - 2f32.powi(-26)), 0x0400);
+        assert_eq!(f32_to_half(1.0e-10), 0x0000);
+
+        for &half in [0x0001, 0x03FF, 0x0400, 0x3555, 0x3C00, 0x7BFF, 0xC000].iter() {
+            assert_eq!(f32_to_half(half_to_f32(half)), half);
+        }
+    }
+
+    #[test]
+    fn rgba32f_falls_back_to_half_floats() {
+        let texel = [0.5f32, 2.0, -1.0, 1.0];
+        let bytes: Vec<u8> = texel
+            .iter()
+            .cycle()
+            .take(4 * 6)
+            .flat_map(|float| float.to_bits().to_le_bytes().to_vec())
+            .collect();
+        let cubemap = Cubemap {
+            format: PixelFormat::Rgba32Float,
+            face_size: 1,
+            levels: vec![bytes],
+        };
+
+        let fallback = cubemap.fallback();
+        assert_eq!(fallback.format, PixelFormat::Rgba16Float);
+        assert_eq!(fallback.face_size, 1);
+        assert_eq!(fallback.levels.len(), 1);
+        assert_eq!(fallback.levels[0].len(), 6 * 8);
+        assert_eq!(
+            &fallback.levels[0][..8],
+            &[0x00, 0x38, 0x00, 0x40, 0x00, 0xBC, 0x00, 0x3C]
+        );
+    }
+
+    #[test]
+    fn level_offset_past_the_end() {
+        let mut bytes = ktx2(4, 1);
+        put_u64(&mut bytes, 80, u64::max_value());
+        assert_eq!(
+            Cubemap::from_bytes(&bytes).err(),
+            Some(ContainerError::Truncated)
+        );
+    }
+}
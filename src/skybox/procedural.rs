@@ -0,0 +1,173 @@
+use super::{encode_srgb, face_direction};
+use cgmath::{InnerSpace, Vector3};
+use image::RgbaImage;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Preetham, Shirley and Smits' "A Practical Analytic Model for Daylight".
+/// cheap enough to regenerate every face on the cpu whenever the sun moves.
+#[derive(Copy, Clone, Debug)]
+pub struct ProceduralSky {
+    /// radians around +Y, measured from +Z towards +X.
+    pub sun_azimuth: f32,
+    /// radians above the horizon.
+    pub sun_elevation: f32,
+    /// haziness of the atmosphere, 2 is a clear day and 10 is murky.
+    pub turbidity: f32,
+    /// width of each generated face in texels.
+    pub face_size: u32,
+}
+
+/// the perez distribution coefficients A through E for one of luminance, x or y.
+type Perez = [f32; 5];
+
+/// the perez sky distribution for a view `theta` from the zenith and `gamma` from the sun.
+fn perez(coefficients: &Perez, theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / theta.cos().max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// zenith chromaticity, a cubic in the sun's zenith angle weighted by a quadratic in turbidity.
+fn zenith_chromaticity(rows: &[[f32; 4]; 3], turbidity: f32, theta_sun: f32) -> f32 {
+    let t = [turbidity * turbidity, turbidity, 1.0];
+    let s = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+    rows.iter()
+        .zip(t.iter())
+        .map(|(row, t)| t * row.iter().zip(s.iter()).map(|(r, s)| r * s).sum::<f32>())
+        .sum()
+}
+
+static ZENITH_X: [[f32; 4]; 3] = [
+    [0.001_66, -0.003_75, 0.002_09, 0.0],
+    [-0.029_03, 0.063_77, -0.032_02, 0.003_94],
+    [0.116_93, -0.211_96, 0.060_52, 0.258_86],
+];
+
+static ZENITH_Y: [[f32; 4]; 3] = [
+    [0.002_75, -0.006_10, 0.003_17, 0.0],
+    [-0.042_14, 0.089_70, -0.041_53, 0.005_16],
+    [0.153_46, -0.267_56, 0.066_70, 0.266_88],
+];
+
+/// angular radius of the drawn sun disc, exaggerated so it shows up at skybox resolution.
+static SUN_RADIUS: f32 = 0.02;
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        ProceduralSky {
+            sun_azimuth: 0.5,
+            sun_elevation: 0.6,
+            turbidity: 3.0,
+            face_size: 128,
+        }
+    }
+}
+
+impl ProceduralSky {
+    /// unit vector pointing at the sun, +Y is up.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let elevation = self.sun_elevation.max(0.0).min(FRAC_PI_2);
+        Vector3::new(
+            elevation.cos() * self.sun_azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * self.sun_azimuth.cos(),
+        )
+    }
+
+    /// move the sun, keeping it between the horizon and the zenith.
+    pub fn rotate_sun(&mut self, azimuth: f32, elevation: f32) {
+        self.sun_azimuth = (self.sun_azimuth + azimuth) % (2.0 * PI);
+        self.sun_elevation = (self.sun_elevation + elevation).max(0.0).min(FRAC_PI_2);
+    }
+
+    /// tone mapped linear rgb seen looking along `dir`.
+    pub fn radiance(&self, dir: Vector3<f32>) -> Vector3<f32> {
+        let t = self.turbidity;
+        let sun = self.sun_direction();
+        let theta_sun = FRAC_PI_2 - self.sun_elevation.max(0.0).min(FRAC_PI_2);
+
+        // the model only covers the upper hemisphere, below it we look at the horizon
+        // and fade to a dark ground.
+        let below = (-dir.y).max(0.0);
+        let above = Vector3::new(dir.x, dir.y.max(0.0), dir.z).normalize();
+        let theta = above.y.max(0.0).min(1.0).acos();
+        let gamma = above.dot(sun).max(-1.0).min(1.0).acos();
+
+        let luminance: Perez = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let x: Perez = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let y: Perez = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_x = zenith_chromaticity(&ZENITH_X, t, theta_sun);
+        let zenith_y = zenith_chromaticity(&ZENITH_Y, t, theta_sun);
+
+        let relative = |coefficients: &Perez| {
+            perez(coefficients, theta, gamma) / perez(coefficients, 0.0, theta_sun)
+        };
+        let big_y = zenith_luminance * relative(&luminance);
+        let small_x = zenith_x * relative(&x);
+        let small_y = zenith_y * relative(&y);
+
+        // expose so the zenith sits comfortably below white, then roll off the highlights.
+        let exposure = 1.0 / (zenith_luminance * 4.0).max(0.5);
+        let big_y = 1.0 - (-big_y * exposure).exp();
+
+        // Yxy -> XYZ -> linear srgb.
+        let big_x = small_x / small_y * big_y;
+        let big_z = (1.0 - small_x - small_y) / small_y * big_y;
+        let mut rgb = Vector3::new(
+            3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z,
+        );
+
+        if gamma < SUN_RADIUS && dir.y >= 0.0 {
+            rgb = Vector3::new(1.0, 1.0, 1.0);
+        }
+
+        let ground = Vector3::new(0.08, 0.07, 0.06) * (1.0 - self.turbidity / 20.0);
+        let fade = (below * 8.0).min(1.0);
+        let rgb = rgb * (1.0 - fade) + ground * fade;
+
+        Vector3::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0))
+    }
+
+    /// the six faces in vulkan's +X, -X, +Y, -Y, +Z, -Z order, as 8 bit srgb.
+    pub fn faces(&self) -> Vec<RgbaImage> {
+        let size = self.face_size;
+        (0..6)
+            .map(|face| {
+                RgbaImage::from_fn(size, size, |x, y| {
+                    let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                    let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                    let rgb = self.radiance(face_direction(face, u, v).normalize());
+                    image::Rgba([
+                        encode_srgb(rgb.x),
+                        encode_srgb(rgb.y),
+                        encode_srgb(rgb.z),
+                        255,
+                    ])
+                })
+            })
+            .collect()
+    }
+}
@@ -1,14 +1,488 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, ImmutableBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
 /// Vulkan imports, these are manifold , low level, and sinful.
-use vulkano::device::{Device, DeviceExtensions};
-use vulkano::instance::{Instance, PhysicalDevice};
-use vulkano::swapchain::{PresentMode, SurfaceTransform, Swapchain};
+use vulkano::device::{Device, DeviceCreationError, DeviceExtensions, Features, Queue};
+use vulkano::format::Format;
+use vulkano::image::SwapchainImage;
+use vulkano::instance::debug::{DebugCallback, Message, MessageTypes};
+use vulkano::instance::{
+    Instance, InstanceCreationError, InstanceExtensions, PhysicalDevice, PhysicalDeviceType,
+};
+use vulkano::swapchain::{
+    Capabilities, CapabilitiesError, ColorSpace, PresentMode, Surface, SurfaceTransform, Swapchain,
+    SwapchainCreationError,
+};
+use vulkano::sync;
+use vulkano::sync::{FenceSignalFuture, GpuFuture};
 use vulkano_win::VkSurfaceBuild;
+use winit::Window;
 
+/// everything that can go wrong getting a window on screen, in the order it's tried.
 #[derive(Debug)]
 pub enum VKError {
+    /// the vulkan library couldn't be loaded or refused to start.
+    VKInstanceError(InstanceCreationError),
+    /// the window couldn't be opened or given a vulkan surface.
+    VKSurfaceError(vulkano_win::CreationError),
+    /// the window has no size to draw at.
     VKWindowError,
-    //VKDeviceError,
+    /// no adapter fit the bill, or none matched the one asked for.
+    VKDeviceError(String),
+    /// the named adapter has no queue that can both draw and present to the window.
+    VKQueueError(String),
+    /// the adapter was found but wouldn't open.
+    VKDeviceCreationError(DeviceCreationError),
+    /// the adapter couldn't say what it can present to the window.
+    VKCapabilitiesError(CapabilitiesError),
+    /// the window offers no format in the srgb colour space, the only one vulkano 0.11
+    /// creates swapchains in.
+    VKFormatError,
+    VKSwapchainError(SwapchainCreationError),
+}
+
+impl VKError {
+    /// what to try next, for printing after the error itself.
+    pub fn diagnosis(&self) -> &'static str {
+        match self {
+            VKError::VKInstanceError(InstanceCreationError::LoadingError(_)) => {
+                "the vulkan loader wasn't found. install your graphics driver's vulkan support \
+                 and the loader (libvulkan on linux, vulkan-1.dll on windows, moltenvk on macos)"
+            }
+            VKError::VKInstanceError(InstanceCreationError::IncompatibleDriver) => {
+                "the installed driver doesn't support vulkan, try updating it"
+            }
+            VKError::VKInstanceError(_) => "the vulkan loader was found but failed to start",
+            VKError::VKSurfaceError(_) | VKError::VKWindowError => {
+                "check there's a display to open the window on"
+            }
+            VKError::VKDeviceError(_) | VKError::VKQueueError(_) => {
+                "run with --list-devices to see what's available, and pick one with --device"
+            }
+            VKError::VKDeviceCreationError(_) => {
+                "the driver may be out of date, or try another adapter with --device"
+            }
+            VKError::VKCapabilitiesError(_)
+            | VKError::VKFormatError
+            | VKError::VKSwapchainError(_) => {
+                "the adapter can't present to this window, try another with --device"
+            }
+        }
+    }
+}
+
+impl fmt::Display for VKError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VKError::VKInstanceError(e) => write!(f, "could not create a vulkan instance: {}", e),
+            VKError::VKSurfaceError(e) => write!(f, "could not open a window: {}", e),
+            VKError::VKWindowError => write!(f, "the window has no size"),
+            VKError::VKDeviceError(e) => write!(f, "{}", e),
+            VKError::VKQueueError(name) => write!(f, "{} can't draw to the window", name),
+            VKError::VKDeviceCreationError(e) => write!(f, "could not open the device: {}", e),
+            VKError::VKCapabilitiesError(e) => {
+                write!(f, "could not query the window's surface: {}", e)
+            }
+            VKError::VKFormatError => write!(f, "the window has no srgb colour space format"),
+            VKError::VKSwapchainError(e) => write!(f, "could not create the swapchain: {}", e),
+        }
+    }
+}
+
+impl Error for VKError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match self {
+            VKError::VKInstanceError(e) => Some(e),
+            VKError::VKSurfaceError(e) => Some(e),
+            VKError::VKDeviceCreationError(e) => Some(e),
+            VKError::VKCapabilitiesError(e) => Some(e),
+            VKError::VKSwapchainError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// the validation layers to look for, newest first. the lunarg meta layer was
+/// replaced by the khronos one in the 1.1.106 sdk.
+static VALIDATION_LAYERS: [&str; 2] = [
+    "VK_LAYER_KHRONOS_validation",
+    "VK_LAYER_LUNARG_standard_validation",
+];
+
+/// the least serious validation message worth printing, everything worse gets through too.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// legal but probably slow.
+    Performance,
+    Info,
+    Debug,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "error" => Ok(Severity::Error),
+            "warning" => Ok(Severity::Warning),
+            "performance" => Ok(Severity::Performance),
+            "info" => Ok(Severity::Info),
+            "debug" => Ok(Severity::Debug),
+            _ => Err(format!("unknown severity {}", s)),
+        }
+    }
+}
+
+impl Severity {
+    fn message_types(self) -> MessageTypes {
+        MessageTypes {
+            error: true,
+            warning: self >= Severity::Warning,
+            performance_warning: self >= Severity::Performance,
+            information: self >= Severity::Info,
+            debug: self >= Severity::Debug,
+        }
+    }
+}
+
+/// create the instance, with the validation layers and a callback printing what they
+/// find at or above `validation` if it's given. validation is best effort, if the
+/// layers aren't installed it says so and carries on without them.
+fn create_instance(
+    validation: Option<Severity>,
+) -> Result<(Arc<Instance>, Option<DebugCallback>), VKError> {
+    let severity = match validation {
+        Some(severity) => severity,
+        None => {
+            let instance = Instance::new(None, &vulkano_win::required_extensions(), None)
+                .map_err(VKError::VKInstanceError)?;
+            return Ok((instance, None));
+        }
+    };
+
+    let available: Vec<String> = vulkano::instance::layers_list()
+        .map(|layers| layers.map(|layer| layer.name().to_owned()).collect())
+        .unwrap_or_default();
+    let layers: Vec<&str> = VALIDATION_LAYERS
+        .iter()
+        .cloned()
+        .filter(|wanted| available.iter().any(|layer| layer == wanted))
+        .take(1)
+        .collect();
+    if layers.is_empty() {
+        eprintln!("the vulkan validation layers aren't installed, carrying on without them");
+    }
+
+    let debug_report = InstanceExtensions::supported_by_core()
+        .map(|supported| supported.ext_debug_report)
+        .unwrap_or(false);
+    let extensions = InstanceExtensions {
+        ext_debug_report: debug_report,
+        ..vulkano_win::required_extensions()
+    };
+    let instance = Instance::new(None, &extensions, &layers).map_err(VKError::VKInstanceError)?;
+    if !debug_report {
+        eprintln!("the vulkan debug report extension isn't available, validation will be silent");
+        return Ok((instance, None));
+    }
+
+    let callback = DebugCallback::new(&instance, severity.message_types(), |message: &Message| {
+        let ty = if message.ty.error {
+            "error"
+        } else if message.ty.warning {
+            "warning"
+        } else if message.ty.performance_warning {
+            "performance"
+        } else if message.ty.information {
+            "info"
+        } else {
+            "debug"
+        };
+        eprintln!(
+            "[vulkan {}] {}: {}",
+            ty, message.layer_prefix, message.description
+        );
+    });
+    match callback {
+        Ok(callback) => Ok((instance, Some(callback))),
+        Err(e) => {
+            eprintln!("could not listen for validation messages: {}", e);
+            Ok((instance, None))
+        }
+    }
+}
+
+/// print every vulkan adapter on the system, marking the one picked by default.
+pub fn list_devices() -> Result<(), VKError> {
+    let (instance, _) = create_instance(None)?;
+    let best = PhysicalDevice::enumerate(&instance)
+        .filter_map(|physical| score(physical).map(|score| (score, physical.index())))
+        .max()
+        .map(|(_, index)| index);
+
+    for physical in PhysicalDevice::enumerate(&instance) {
+        let version = physical.api_version();
+        println!(
+            "{}: {} ({:?}), vulkan {}.{}.{}{}",
+            physical.index(),
+            physical.name(),
+            physical.ty(),
+            version.major,
+            version.minor,
+            version.patch,
+            if Some(physical.index()) == best {
+                ", default"
+            } else {
+                ""
+            }
+        );
+        for heap in physical.memory_heaps() {
+            println!(
+                "    memory heap {}: {} MiB{}",
+                heap.id(),
+                heap.size() / (1024 * 1024),
+                if heap.is_device_local() {
+                    ", device local"
+                } else {
+                    ""
+                }
+            );
+        }
+        for family in physical.queue_families() {
+            let mut supports = Vec::new();
+            if family.supports_graphics() {
+                supports.push("graphics");
+            }
+            if family.supports_compute() {
+                supports.push("compute");
+            }
+            if family.explicitly_supports_transfers() {
+                supports.push("transfer");
+            }
+            if family.supports_sparse_binding() {
+                supports.push("sparse binding");
+            }
+            println!(
+                "    queue family {}: {} queues, {}",
+                family.id(),
+                family.queues_count(),
+                supports.join(" ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// how much we'd like to render on an adapter, or none if it can't render at all.
+/// a real gpu beats one sharing the cpu's memory beats emulating one on the cpu,
+/// and between two of a kind the one with more of its own memory wins.
+fn score(physical: PhysicalDevice) -> Option<(u32, usize)> {
+    if !physical.queue_families().any(|q| q.supports_graphics())
+        || !physical
+            .supported_features()
+            .superset_of(&required_features())
+    {
+        return None;
+    }
+    let kind = match physical.ty() {
+        PhysicalDeviceType::DiscreteGpu => 4,
+        PhysicalDeviceType::IntegratedGpu => 3,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 1,
+        PhysicalDeviceType::Other => 0,
+    };
+    let local_memory = physical
+        .memory_heaps()
+        .filter(|heap| heap.is_device_local())
+        .map(|heap| heap.size())
+        .sum();
+    Some((kind, local_memory))
+}
+
+/// the present mode for a `--present-mode` name.
+pub fn parse_present_mode(name: &str) -> Result<PresentMode, String> {
+    match name {
+        "fifo" => Ok(PresentMode::Fifo),
+        "mailbox" => Ok(PresentMode::Mailbox),
+        "immediate" => Ok(PresentMode::Immediate),
+        "relaxed" => Ok(PresentMode::Relaxed),
+        _ => Err(format!("unknown present mode {}", name)),
+    }
+}
+
+/// whether frames wait for the display's refresh, tearing at most when one's late.
+pub fn is_vsync(mode: PresentMode) -> bool {
+    match mode {
+        PresentMode::Fifo | PresentMode::Relaxed => true,
+        PresentMode::Mailbox | PresentMode::Immediate => false,
+    }
+}
+
+/// `wanted` if the surface supports it, otherwise the closest thing that it does.
+/// fifo is the only mode every surface has to support, so everything ends up there.
+fn choose_present_mode(caps: &Capabilities, wanted: PresentMode) -> PresentMode {
+    let fallbacks: &[PresentMode] = match wanted {
+        // both uncapped, mailbox just doesn't tear.
+        PresentMode::Immediate => &[PresentMode::Immediate, PresentMode::Mailbox],
+        PresentMode::Mailbox => &[PresentMode::Mailbox, PresentMode::Immediate],
+        PresentMode::Relaxed => &[PresentMode::Relaxed],
+        PresentMode::Fifo => &[],
+    };
+    let mode = fallbacks
+        .iter()
+        .cloned()
+        .find(|&mode| caps.present_modes.supports(mode))
+        .unwrap_or(PresentMode::Fifo);
+    if mode != wanted {
+        println!(
+            "{:?} presentation isn't supported, using {:?}",
+            wanted, mode
+        );
+    }
+    mode
+}
+
+/// formats the hardware encodes to srgb as it writes, best first.
+static SRGB_FORMATS: [Format; 3] = [
+    Format::B8G8R8A8Srgb,
+    Format::R8G8B8A8Srgb,
+    Format::A8B8G8R8SrgbPack32,
+];
+
+/// formats that store what they're given, so the shaders have to do the encoding.
+static UNORM_FORMATS: [Format; 4] = [
+    Format::B8G8R8A8Unorm,
+    Format::R8G8B8A8Unorm,
+    Format::A8B8G8R8UnormPack32,
+    Format::A2B10G10R10UnormPack32,
+];
+
+/// pick the swapchain format. the shaders work in linear light, so an srgb format is
+/// first choice and encodes for free. failing that a plain format works as long as the
+/// shaders encode, which the returned bool says.
+/// only formats offered in the srgb colour space are considered, swapchains are
+/// always created in it.
+fn choose_format(caps: &Capabilities) -> Result<(Format, bool), VKError> {
+    let srgb_space = || {
+        caps.supported_formats
+            .iter()
+            .filter(|&&(_, space)| space == ColorSpace::SrgbNonLinear)
+            .map(|&(format, _)| format)
+    };
+    let offered = |format: Format| srgb_space().any(|supported| supported == format);
+
+    if let Some(&format) = SRGB_FORMATS.iter().find(|&&format| offered(format)) {
+        return Ok((format, false));
+    }
+    if let Some(&format) = UNORM_FORMATS.iter().find(|&&format| offered(format)) {
+        println!(
+            "no srgb swapchain format, encoding {:?} in the shaders",
+            format
+        );
+        return Ok((format, true));
+    }
+    // anything else in the srgb colour space, whatever it is it isn't srgb encoded.
+    let format = srgb_space().next().ok_or(VKError::VKFormatError)?;
+    println!(
+        "no srgb or 8 bit swapchain format, encoding {:?} in the shaders",
+        format
+    );
+    Ok((format, true))
+}
+
+/// a swapchain filling `dimensions` of the window, replacing `old` if there is one.
+fn create_swapchain(
+    device: &Arc<Device>,
+    surface: &Arc<Surface<Window>>,
+    queue: &Arc<Queue>,
+    format: Format,
+    dimensions: [u32; 2],
+    present_mode: PresentMode,
+    old: Option<&Arc<Swapchain<Window>>>,
+) -> Result<(Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>), VKError> {
+    let caps = surface
+        .capabilities(device.physical_device())
+        .map_err(VKError::VKCapabilitiesError)?;
+    let usage = caps.supported_usage_flags;
+    // the spec guarantees at least one composite alpha mode.
+    let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+
+    // mailbox needs an image spare to replace while one's on screen and one's drawn to.
+    let mut image_count = match present_mode {
+        PresentMode::Mailbox => caps.min_image_count + 1,
+        _ => caps.min_image_count,
+    };
+    if let Some(max) = caps.max_image_count {
+        image_count = image_count.min(max);
+    }
+
+    Swapchain::new(
+        device.clone(),
+        surface.clone(),
+        image_count,
+        format,
+        dimensions,
+        1,
+        usage,
+        queue,
+        SurfaceTransform::Identity,
+        alpha,
+        present_mode,
+        true,
+        old,
+    )
+    .map_err(VKError::VKSwapchainError)
+}
+
+/// the features the renderer can't run without. everything it does is core vulkan so far,
+/// this is where to add anything that changes.
+fn required_features() -> Features {
+    Features::none()
+}
+
+/// the features to enable on `physical`, the required ones plus whichever of the optional
+/// ones it has. it's told which are missing and what won't work without them.
+fn device_features(physical: PhysicalDevice) -> Features {
+    let supported = physical.supported_features();
+    let optional = [
+        (
+            supported.sampler_anisotropy,
+            "sampler anisotropy",
+            "the skybox will look blurrier at glancing angles",
+        ),
+        (
+            supported.fill_mode_non_solid,
+            "non solid fill modes",
+            "there's no wireframe view",
+        ),
+        (
+            supported.texture_compression_bc,
+            "bc texture compression",
+            "compressed skyboxes are decompressed on the cpu",
+        ),
+    ];
+    for &(has, name, consequence) in optional.iter() {
+        if !has {
+            println!(
+                "{} doesn't support {}, {}",
+                physical.name(),
+                name,
+                consequence
+            );
+        }
+    }
+
+    Features {
+        sampler_anisotropy: supported.sampler_anisotropy,
+        fill_mode_non_solid: supported.fill_mode_non_solid,
+        texture_compression_bc: supported.texture_compression_bc,
+        ..required_features()
+    }
 }
 
 /// main struct that holds the initiliazed vulkan values.
@@ -21,28 +495,85 @@ pub struct VKState {
     pub device: Arc<vulkano::device::Device>,
     /// async reference the the command queue, we buffer commands in to this then run them async with a fence
     pub queue: Arc<vulkano::device::Queue>,
+    /// uploads go here, a dedicated transfer queue if there is one, otherwise `queue`.
+    pub transfer_queue: Arc<vulkano::device::Queue>,
     /// main winit event loop. controls things like user input
     pub events_loop: winit::EventsLoop,
     /// TODO : figure out what this actually does
     pub surface: Arc<vulkano::swapchain::Surface<winit::Window>>,
     // window dimensions
     pub dimensions: [u32; 2],
+    /// how finished frames are queued for the display, after falling back to what's supported.
+    pub present_mode: PresentMode,
+    /// the swapchain isn't srgb, so the shaders have to encode their output themselves.
+    pub encode_srgb: bool,
+    /// prints validation messages for as long as it's alive, when validating.
+    _debug_callback: Option<DebugCallback>,
 }
 
 impl VKState {
-    /// set up the vulkan environment. may panic in a misconfigured environment,
-    /// make sure you have the vulkan library somewhere in your path.
-    pub fn vk_init() -> Result<Self, VKError> {
-        let extensions = vulkano_win::required_extensions();
-        let instance = Instance::new(None, &extensions, None).unwrap();
+    /// set up the vulkan environment, make sure you have the vulkan library
+    /// somewhere in your path.
+    /// `device` is an index from `list_devices` or part of an adapter's name,
+    /// without one the best scoring adapter that can draw to the window is used.
+    /// `validation` turns on the validation layers, printing messages this severe or worse.
+    /// `present_mode` is used if the window supports it, with a fallback if not.
+    pub fn vk_init(
+        device: Option<&str>,
+        validation: Option<Severity>,
+        present_mode: PresentMode,
+    ) -> Result<Self, VKError> {
+        let (instance, debug_callback) = create_instance(validation)?;
 
-        let physical = PhysicalDevice::enumerate(&instance).next().unwrap();
         let events_loop = winit::EventsLoop::new();
         let surface = winit::WindowBuilder::new()
             .build_vk_surface(&events_loop, instance.clone())
-            .unwrap();
+            .map_err(VKError::VKSurfaceError)?;
         let window = surface.window();
 
+        let presents = |physical: &PhysicalDevice| {
+            physical
+                .queue_families()
+                .any(|q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
+        };
+        let physical = match device {
+            Some(wanted) => {
+                let physical = match wanted.parse::<usize>() {
+                    Ok(index) => PhysicalDevice::from_index(&instance, index),
+                    Err(_) => {
+                        let wanted = wanted.to_lowercase();
+                        PhysicalDevice::enumerate(&instance)
+                            .find(|physical| physical.name().to_lowercase().contains(&wanted))
+                    }
+                }
+                .ok_or_else(|| {
+                    VKError::VKDeviceError(format!("no device matches \"{}\"", wanted))
+                })?;
+                if !presents(&physical) {
+                    return Err(VKError::VKQueueError(physical.name()));
+                }
+                if !physical
+                    .supported_features()
+                    .superset_of(&required_features())
+                {
+                    return Err(VKError::VKDeviceError(format!(
+                        "{} lacks features the renderer needs",
+                        physical.name()
+                    )));
+                }
+                physical
+            }
+            None => PhysicalDevice::enumerate(&instance)
+                .filter(presents)
+                .filter_map(|physical| score(physical).map(|score| (score, physical.index())))
+                .max()
+                .and_then(|(_, index)| PhysicalDevice::from_index(&instance, index))
+                .ok_or_else(|| {
+                    VKError::VKDeviceError("no device can draw to the window".to_owned())
+                })?,
+        };
+        println!("using {} ({:?})", physical.name(), physical.ty());
+
         // unlike the triangle example we need to keep track of the width and height so we can calculate
         // render the teapot with the correct aspect ratio.
         let dimensions = if let Some(dimensions) = window.get_inner_size() {
@@ -55,54 +586,237 @@ impl VKState {
         let queue_family = physical
             .queue_families()
             .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
-            .unwrap();
+            .ok_or_else(|| VKError::VKQueueError(physical.name()))?;
 
         let device_ext = DeviceExtensions {
             khr_swapchain: true,
             ..DeviceExtensions::none()
         };
 
+        // a family that only does transfers is usually the gpu's copy engine,
+        // which can upload while the graphics queue gets on with drawing.
+        let transfer_family = physical.queue_families().find(|&q| {
+            q.explicitly_supports_transfers() && !q.supports_graphics() && !q.supports_compute()
+        });
+        let mut queue_families = vec![(queue_family, 0.5)];
+        if let Some(transfer_family) = transfer_family {
+            queue_families.push((transfer_family, 0.5));
+        }
+
         let (device, mut queues) = Device::new(
             physical,
-            physical.supported_features(),
+            &device_features(physical),
             &device_ext,
-            [(queue_family, 0.5)].iter().cloned(),
+            queue_families,
         )
-        .unwrap();
-        let caps = surface.capabilities(physical).unwrap();
-        let usage = caps.supported_usage_flags;
-        let format = caps.supported_formats[0].0;
-        let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+        .map_err(VKError::VKDeviceCreationError)?;
 
+        // one queue was asked for from each family, and they come back in the same order.
         let queue = queues.next().unwrap();
+        let transfer_queue = queues.next().unwrap_or_else(|| queue.clone());
 
-        let (swapchain, images) = {
-            Swapchain::new(
-                device.clone(),
-                surface.clone(),
-                caps.min_image_count,
-                format,
-                dimensions,
-                1,
-                usage,
-                &queue,
-                SurfaceTransform::Identity,
-                alpha,
-                PresentMode::Fifo,
-                true,
-                None,
-            )
-            .unwrap()
-        };
+        let caps = surface
+            .capabilities(physical)
+            .map_err(VKError::VKCapabilitiesError)?;
+        let present_mode = choose_present_mode(&caps, present_mode);
+        let (format, encode_srgb) = choose_format(&caps)?;
+        let (swapchain, images) = create_swapchain(
+            &device,
+            &surface,
+            &queue,
+            format,
+            dimensions,
+            present_mode,
+            None,
+        )?;
 
         Ok(VKState {
             images,
             swapchain,
             device,
             queue,
+            transfer_queue,
             surface,
             events_loop,
             dimensions,
+            present_mode,
+            encode_srgb,
+            _debug_callback: debug_callback,
         })
     }
+
+    /// replace the swapchain with one `dimensions` big, in the current present mode.
+    /// the surface has the final say on the size, so check `dimensions` afterwards.
+    pub fn recreate_swapchain(&mut self, dimensions: [u32; 2]) -> Result<(), VKError> {
+        let (swapchain, images) = create_swapchain(
+            &self.device,
+            &self.surface,
+            &self.queue,
+            self.swapchain.format(),
+            dimensions,
+            self.present_mode,
+            Some(&self.swapchain),
+        )?;
+        self.dimensions = swapchain.dimensions();
+        self.swapchain = swapchain;
+        self.images = images;
+        Ok(())
+    }
+
+    /// copy `data` in to a device local buffer through a staging buffer on the transfer queue.
+    /// `usage` only needs to say how it's drawn with. the copy is submitted straight away,
+    /// and the returned future has to finish before the buffer is used.
+    pub fn upload<T, I>(
+        &self,
+        data: I,
+        usage: BufferUsage,
+    ) -> (Arc<ImmutableBuffer<[T]>>, Box<GpuFuture>)
+    where
+        T: Send + Sync + 'static,
+        I: ExactSizeIterator<Item = T>,
+    {
+        let staging = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::transfer_source(),
+            data,
+        )
+        .unwrap();
+
+        // the transfer queue writes it and the graphics queue reads it, so it's shared
+        // between both families rather than having its ownership handed over.
+        let mut families = vec![self.queue.family()];
+        if self.transfer_queue.family().id() != self.queue.family().id() {
+            families.push(self.transfer_queue.family());
+        }
+        let usage = BufferUsage {
+            transfer_destination: true,
+            ..usage
+        };
+        let (buffer, destination) = unsafe {
+            ImmutableBuffer::uninitialized_array(
+                self.device.clone(),
+                staging.len(),
+                usage,
+                families,
+            )
+            .unwrap()
+        };
+
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.transfer_queue.family(),
+        )
+        .unwrap()
+        .copy_buffer(staging, destination)
+        .unwrap()
+        .build()
+        .unwrap();
+        // vulkano can only sync futures from different queues through a semaphore, so the
+        // copy is flushed here and signals one for the graphics queue work that waits on it.
+        let future = command_buffer
+            .execute(self.transfer_queue.clone())
+            .unwrap()
+            .then_signal_semaphore_and_flush()
+            .unwrap();
+        (buffer, Box::new(future))
+    }
+
+    /// switch between waiting for the display's refresh and presenting as fast as possible.
+    /// takes effect when the swapchain is next recreated.
+    pub fn toggle_vsync(&mut self) -> Result<(), VKError> {
+        let caps = self
+            .surface
+            .capabilities(self.device.physical_device())
+            .map_err(VKError::VKCapabilitiesError)?;
+        let wanted = if is_vsync(self.present_mode) {
+            PresentMode::Immediate
+        } else {
+            PresentMode::Fifo
+        };
+        self.present_mode = choose_present_mode(&caps, wanted);
+        Ok(())
+    }
+}
+
+/// how many frames the cpu may record ahead of the gpu. two lets one frame be drawn
+/// while the next is filled in, any more only adds latency.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+/// one frame in flight, the resources it draws with and the fence the gpu signals
+/// once it's done with them.
+struct FrameContext<T> {
+    resources: T,
+    fence: Option<FenceSignalFuture<Box<GpuFuture>>>,
+}
+
+/// a ring of per frame resources that the frames take turns with. `begin` waits for
+/// the gpu to finish the last frame that used the next set, so they can be rewritten
+/// while the others are still being drawn.
+pub struct FrameContexts<T> {
+    device: Arc<Device>,
+    frames: Vec<FrameContext<T>>,
+    current: usize,
+    /// gpu work the next frame has to wait on, like a texture upload.
+    pending: Option<Box<GpuFuture>>,
+}
+
+impl<T> FrameContexts<T> {
+    /// `FRAMES_IN_FLIGHT` contexts with resources from `create`.
+    pub fn new<F>(device: Arc<Device>, mut create: F) -> Self
+    where
+        F: FnMut() -> T,
+    {
+        FrameContexts {
+            device,
+            frames: (0..FRAMES_IN_FLIGHT)
+                .map(|_| FrameContext {
+                    resources: create(),
+                    fence: None,
+                })
+                .collect(),
+            current: 0,
+            pending: None,
+        }
+    }
+
+    /// make the next frame wait for `future` before it starts.
+    pub fn wait_for(&mut self, future: Box<GpuFuture>) {
+        self.pending = Some(match self.pending.take() {
+            Some(pending) => Box::new(pending.join(future)) as Box<_>,
+            None => future,
+        });
+    }
+
+    /// move on to the next frame, blocking until the gpu is done with its resources.
+    /// returns them along with the future the frame's commands should follow.
+    pub fn begin(&mut self) -> (&mut T, Box<GpuFuture>) {
+        for frame in self.frames.iter_mut() {
+            if let Some(ref mut fence) = frame.fence {
+                fence.cleanup_finished();
+            }
+        }
+
+        self.current = (self.current + 1) % self.frames.len();
+        let frame = &mut self.frames[self.current];
+        if let Some(fence) = frame.fence.take() {
+            // dropping the finished future is what lets go of the buffers it used.
+            fence.wait(None).unwrap();
+        }
+
+        let start = match self.pending.take() {
+            Some(pending) => pending,
+            None => Box::new(sync::now(self.device.clone())) as Box<_>,
+        };
+        (&mut frame.resources, start)
+    }
+
+    /// hand over the fence for the frame from the last `begin`, if it was submitted.
+    pub fn end(&mut self, fence: Option<FenceSignalFuture<Box<GpuFuture>>>) {
+        self.frames[self.current].fence = fence;
+    }
+
+    /// every frame's resources, to update them all when something they share changes.
+    pub fn resources_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.frames.iter_mut().map(|frame| &mut frame.resources)
+    }
 }